@@ -1,6 +1,28 @@
-use std::collections::HashMap;
+use encoding_rs::{Encoding, UTF_8};
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use std::io::{self, Read};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use unicode_segmentation::UnicodeSegmentation;
+
+// Ordre de résolution: --input > --stdin > texte positionnel > stdin (pipe)
+#[derive(Debug, Clone)]
+enum Input {
+    File(PathBuf),
+    Stdin,
+    Text(String),
+}
+
+// Ordre de la liste affichée; --top s'applique après le tri
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortMode {
+    Freq,
+    Alpha,
+    AlphaDesc,
+}
 
 #[derive(Debug, Clone)]
 struct Config {
@@ -8,7 +30,34 @@ struct Config {
     min_length: usize,
     ignore_case: bool,
     top_was_set: bool,
-    input_text: Option<String>,
+    prefix: Option<String>,
+    suffix: Option<String>,
+    markdown: bool,
+    jsonl: bool,
+    progress: bool,
+    dedup_lines: bool,
+    head_tail: Option<usize>,
+    top_per_length: Option<usize>,
+    zipf: bool,
+    encoding: &'static Encoding,
+    bucket_numbers: bool,
+    buckets: Vec<(String, Regex)>,
+    diff: Option<PathBuf>,
+    percent: bool,
+    digest: bool,
+    entropy: bool,
+    min_unique_ratio: Option<f64>,
+    only: Option<HashSet<String>>,
+    replace: Option<HashMap<String, String>>,
+    show_zero: bool,
+    trim_mode: bool,
+    squash_repeats: Option<usize>,
+    max_word_width: Option<usize>,
+    grapheme_length: bool,
+    stem: bool,
+    sort: SortMode,
+    collocations: bool,
+    input: Input,
 }
 
 fn print_help() {
@@ -16,13 +65,75 @@ fn print_help() {
     println!("Count word frequency in text\n");
     println!("Arguments:");
     println!("  Text to analyze (or use stdin)\n");
+    println!("Input precedence: --input > --stdin > text arguments > piped stdin\n");
     println!("Options:");
     println!("  --top N            Show top N words [default: 10]");
     println!("  --min-length N     Ignore words shorter than N [default: 1]");
     println!("  --ignore-case      Case insensitive counting");
+    println!("  --prefix STR       Only count words starting with STR");
+    println!("  --suffix STR       Only count words ending with STR");
+    println!("  --trim-mode        Split on whitespace only, trimming punctuation at token edges");
+    println!(
+        "  --squash-repeats N Collapse runs of a repeated character to N (\"sooooo\" -> \"soo\")"
+    );
+    println!("  --stem             Merge inflected forms by stripping -s/-es/-ed/-ing (heuristic)");
+    println!("  --replace FILE     Rewrite tokens before counting (one 'from<TAB>to' per line)");
+    println!("  --only FILE        Count only the words listed in FILE (one per line)");
+    println!("  --show-zero        With --only, also list allowed words that never appear");
+    println!("  --bucket-numbers   Count all numeric tokens as a single <NUMBER> entry");
+    println!(
+        "  --bucket-by N=RE   Count tokens matching regex RE as a single <N> entry (repeatable)"
+    );
+    println!("  --diff FILE        Show the words whose count changed most from the input to FILE");
+    println!("  --percent          Show each word's share of the total word count");
+    println!("  --digest           Print an FNV-1a hash of the ranked word:count lines");
+    println!("  --entropy          Print the Shannon entropy (bits) of all counted words");
+    println!(
+        "  --min-unique-ratio F  Print unique/total words and exit 1 if it is below F (0 to 1)"
+    );
+    println!(
+        "  --sort MODE        freq (count desc), alpha or alpha-desc; --top still applies [default: freq]"
+    );
+    println!(
+        "  --collocations     Rank adjacent word pairs by PMI instead of counting words (pairs seen twice+)"
+    );
+    println!(
+        "  --max-word-width N Truncate displayed words to N characters with '…' (counts unaffected)"
+    );
+    println!(
+        "  --grapheme-length  Measure words in grapheme clusters (emoji, accents) for --min-length and --max-word-width"
+    );
+    println!("  --markdown         Print results as a markdown table");
+    println!(
+        "  --jsonl            Print one {{\"word\":...,\"count\":...}} object per line, no header"
+    );
+    println!("  --head-tail N      Show the N most and N least frequent words, split by '...'");
+    println!("  --top-per-length K Show the top K words of each word length, under 'Length N:'");
+    println!("  --zipf             Compare the top N counts to Zipf's law (replaces the list)");
+    println!("  --input FILE       Read text from FILE");
+    println!("  --stdin            Read stdin even if text arguments are given");
+    println!(
+        "  --encoding ENC     Decode stdin/FILE as ENC (e.g. latin1, utf16le) [default: utf-8]"
+    );
+    println!("  --progress         Report bytes read and words counted on stderr while working");
+    println!(
+        "  --dedup-lines      Drop repeated input lines (first kept) before counting; reports the count on stderr"
+    );
+    println!(
+        "                     (keeps every distinct line in memory: needs RAM in proportion to unique lines)"
+    );
     println!("  -h, --help         Print help");
 }
 
+fn parse_ratio_opt(flag: &str, raw: &str) -> f64 {
+    match raw.parse::<f64>() {
+        Ok(f) if (0.0..=1.0).contains(&f) => f,
+        _ => usage_error(&format!(
+            "{flag} expects a number between 0 and 1, got '{raw}'"
+        )),
+    }
+}
+
 fn usage_error(msg: &str) -> ! {
     eprintln!("error: {msg}");
     std::process::exit(2);
@@ -47,17 +158,521 @@ fn is_word_char(c: char) -> bool {
     c.is_alphanumeric() || matches!(c, '\'' | '"' | '’' | '“' | '”')
 }
 
+// --trim-mode: découpage sur les espaces, ponctuation retirée seulement aux extrémités
+// ("U.S.A.," -> "U.S.A", "well-being" reste entier)
+fn trim_token(token: &str) -> &str {
+    token.trim_matches(|c: char| !c.is_alphanumeric())
+}
+
+// Réduit toute répétition d'un même caractère au-delà de `max` à `max` occurrences
+fn squash_repeats(token: &str, max: Option<usize>) -> Cow<'_, str> {
+    let Some(max) = max else {
+        return Cow::Borrowed(token);
+    };
+    let mut out = String::with_capacity(token.len());
+    let mut prev = None;
+    let mut run = 0usize;
+    for c in token.chars() {
+        run = if prev == Some(c) { run + 1 } else { 1 };
+        prev = Some(c);
+        if run <= max {
+            out.push(c);
+        }
+    }
+    if out.len() == token.len() {
+        Cow::Borrowed(token)
+    } else {
+        Cow::Owned(out)
+    }
+}
+
+// Affichage seulement: au-delà de `max` caractères (ou grappes avec
+// --grapheme-length), le mot est coupé et finit par '…'
+fn clip_word(word: &str, max: Option<usize>, graphemes: bool) -> Cow<'_, str> {
+    let Some(max) = max else {
+        return Cow::Borrowed(word);
+    };
+    let len = if graphemes {
+        word.graphemes(true).count()
+    } else {
+        word.chars().count()
+    };
+    if len <= max {
+        return Cow::Borrowed(word);
+    }
+    let mut out: String = if graphemes {
+        word.graphemes(true).take(max - 1).collect()
+    } else {
+        word.chars().take(max - 1).collect()
+    };
+    out.push('…');
+    Cow::Owned(out)
+}
+
+// Racinisation grossière de l'anglais (-s, -es, -ed, -ing), pas un Porter
+fn stem(word: &str) -> String {
+    // Radical trop court ("sing", "need", "bus"): on garde le mot tel quel
+    let long_enough = |base: &str| base.chars().count() >= 3;
+
+    for suffix in ["ing", "ed"] {
+        if let Some(base) = word.strip_suffix(suffix)
+            && long_enough(base)
+            && base.chars().any(is_vowel)
+        {
+            return undouble(base).to_string();
+        }
+    }
+
+    if let Some(base) = word.strip_suffix("es")
+        && long_enough(base)
+        && ["s", "x", "z", "ch", "sh"]
+            .iter()
+            .any(|e| base.ends_with(e))
+    {
+        return base.to_string();
+    }
+
+    if let Some(base) = word.strip_suffix('s')
+        && long_enough(base)
+        && !base.ends_with('s')
+    {
+        return base.to_string();
+    }
+
+    word.to_string()
+}
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y')
+}
+
+// "runn" -> "run", "stopp" -> "stop"; "fall", "miss", "buzz" restent doublés
+fn undouble(base: &str) -> &str {
+    let mut rev = base.chars().rev();
+    match (rev.next(), rev.next()) {
+        (Some(a), Some(b)) if a == b && a.is_alphabetic() && !is_vowel(a) && !"lsz".contains(a) => {
+            &base[..base.len() - a.len_utf8()]
+        }
+        _ => base,
+    }
+}
+
 // min-length doit compter les caractères “utiles” (alphanum), pas les quotes
 fn core_len(token: &str) -> usize {
     token.chars().filter(|c| c.is_alphanumeric()).count()
 }
 
-fn read_stdin_lossy() -> String {
+// Plages emoji courantes (symboles, pictogrammes, drapeaux); pas la table
+// Unicode complète, mais de quoi garder les emoji dans les mots
+fn is_pictographic(c: char) -> bool {
+    matches!(c, '\u{2600}'..='\u{27BF}' | '\u{1F000}'..='\u{1FAFF}')
+}
+
+// Une grappe compte par son caractère de base: "e" + accent combinant,
+// un drapeau ou une famille emoji (ZWJ) valent chacun 1
+fn is_word_grapheme(g: &str) -> bool {
+    g.starts_with(|c: char| c.is_alphanumeric() || is_pictographic(c))
+}
+
+// Équivalent de core_len avec --grapheme-length
+fn grapheme_core_len(token: &str) -> usize {
+    token
+        .graphemes(true)
+        .filter(|g| is_word_grapheme(g))
+        .count()
+}
+
+// Découpage de --grapheme-length: comme is_word_char, mais par grappe, pour ne
+// pas séparer un accent combinant ou un emoji de son mot
+fn grapheme_tokens(text: &str) -> impl Iterator<Item = &str> {
+    let mut start = 0;
+    text.grapheme_indices(true)
+        .filter(|(_, g)| !is_word_grapheme(g) && !g.starts_with(is_word_char))
+        .map(|(i, g)| (i, i + g.len()))
+        .chain(std::iter::once((text.len(), text.len())))
+        .map(move |(at, next)| {
+            let token = &text[start..at];
+            start = next;
+            token
+        })
+}
+
+// trim_token par grappes: "(👍👍)" garde ses emoji, "café," perd la virgule
+fn trim_token_graphemes(token: &str) -> &str {
+    let start = token
+        .grapheme_indices(true)
+        .find(|(_, g)| is_word_grapheme(g))
+        .map_or(token.len(), |(i, _)| i);
+    let end = token
+        .grapheme_indices(true)
+        .rfind(|(_, g)| is_word_grapheme(g))
+        .map_or(start, |(i, g)| i + g.len());
+    &token[start..end]
+}
+
+// Table de remplacement: "from<TAB>to" par ligne, lignes vides ignorées
+fn read_replacements(
+    path: &PathBuf,
+    encoding: &'static Encoding,
+    fold: bool,
+) -> HashMap<String, String> {
+    let fold = |w: &str| {
+        if fold {
+            w.to_lowercase()
+        } else {
+            w.to_string()
+        }
+    };
+    read_file_lossy(path, encoding)
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let (from, to) = line.split_once('\t').unwrap_or_else(|| {
+                usage_error(&format!(
+                    "{}:{}: expected 'from<TAB>to', got '{line}'",
+                    path.display(),
+                    i + 1
+                ))
+            });
+            (fold(from.trim()), fold(to.trim()))
+        })
+        .collect()
+}
+
+// Liste blanche: un mot par ligne, lignes vides ignorées
+fn read_allowlist(path: &PathBuf, encoding: &'static Encoding, fold: bool) -> HashSet<String> {
+    read_file_lossy(path, encoding)
+        .lines()
+        .map(str::trim)
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            if fold {
+                w.to_lowercase()
+            } else {
+                w.to_string()
+            }
+        })
+        .collect()
+}
+
+fn parse_bucket(raw: &str) -> (String, Regex) {
+    let (name, pattern) = raw
+        .split_once('=')
+        .unwrap_or_else(|| usage_error(&format!("--bucket-by expects NAME=REGEX, got '{raw}'")));
+    if name.is_empty() {
+        usage_error("--bucket-by bucket name is empty");
+    }
+    let re = Regex::new(pattern)
+        .unwrap_or_else(|e| usage_error(&format!("--bucket-by invalid regex '{pattern}': {e}")));
+    (format!("<{name}>"), re)
+}
+
+// Réécrit un token vers son bucket éventuel avant comptage
+fn bucket_for<'a>(word: &'a str, cfg: &'a Config) -> &'a str {
+    if cfg.bucket_numbers && word.chars().all(char::is_numeric) {
+        return "<NUMBER>";
+    }
+    cfg.buckets
+        .iter()
+        .find(|(_, re)| re.is_match(word))
+        .map_or(word, |(name, _)| name.as_str())
+}
+
+fn format_with_commas(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// Part d'un mot dans le total (après filtres), en pourcentage
+fn percent_of(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 * 100.0 / total as f64
+    }
+}
+
+// Les `n` premiers et les `n` derniers éléments; tout en tête (sans queue) s'ils se recouvriraient
+fn split_head_tail<T>(mut items: Vec<T>, n: usize) -> (Vec<T>, Option<Vec<T>>) {
+    if items.len() <= 2 * n {
+        return (items, None);
+    }
+    let tail = items.split_off(items.len() - n);
+    items.truncate(n);
+    (items, Some(tail))
+}
+
+// Regroupe par longueur (comme --min-length) dans l'ordre de `items`, puis garde les `k` premiers
+fn top_per_length(
+    items: Vec<(String, u64)>,
+    k: usize,
+    graphemes: bool,
+) -> BTreeMap<usize, Vec<(String, u64)>> {
+    let word_len = if graphemes {
+        grapheme_core_len
+    } else {
+        core_len
+    };
+    let mut groups: BTreeMap<usize, Vec<(String, u64)>> = BTreeMap::new();
+    for (word, count) in items {
+        let bucket = groups.entry(word_len(&word)).or_default();
+        if bucket.len() < k {
+            bucket.push((word, count));
+        }
+    }
+    groups
+}
+
+// Échappement JSON minimal: guillemets, antislash et caractères de contrôle
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn print_jsonl(items: &[(String, u64)]) {
+    for (word, count) in items {
+        println!("{{\"word\":\"{}\",\"count\":{count}}}", json_escape(word));
+    }
+}
+
+fn print_markdown(
+    items: &[(String, u64)],
+    total: Option<u64>,
+    width: Option<usize>,
+    graphemes: bool,
+) {
+    if total.is_some() {
+        println!("| Word | Count | Percent |");
+        println!("| --- | ---: | ---: |");
+    } else {
+        println!("| Word | Count |");
+        println!("| --- | ---: |");
+    }
+    for (word, count) in items {
+        let word = clip_word(word, width, graphemes).replace('|', "\\|");
+        match total {
+            Some(total) => println!(
+                "| {word} | {} | {:.1}% |",
+                format_with_commas(*count),
+                percent_of(*count, total)
+            ),
+            None => println!("| {word} | {} |", format_with_commas(*count)),
+        }
+    }
+}
+
+// FNV-1a 64 bits sur les lignes canoniques "mot:compte\n", dans l'ordre du classement
+fn digest_items(items: &[(String, u64)]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for (word, count) in items {
+        for b in format!("{word}:{count}\n").bytes() {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+// Entropie de Shannon en bits: -somme(p * log2(p))
+fn entropy_bits(counts: impl Iterator<Item = u64> + Clone) -> f64 {
+    let total = counts.clone().sum::<u64>();
+    if total == 0 {
+        return 0.0;
+    }
+    counts
+        .filter(|&c| c > 0)
+        .map(|c| {
+            let p = c as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+// Mots distincts (hors comptes nuls de --show-zero), total, et leur rapport (0 sans mot)
+fn unique_ratio(freq: &HashMap<String, u64>) -> (usize, u64, f64) {
+    let unique = freq.values().filter(|&&c| c > 0).count();
+    let total = freq.values().sum::<u64>();
+    let ratio = if total == 0 {
+        0.0
+    } else {
+        unique as f64 / total as f64
+    };
+    (unique, total, ratio)
+}
+
+// Affiche le rapport puis, sous le seuil, termine avec le code 1
+fn check_unique_ratio((unique, total, ratio): (usize, u64, f64), min: f64) {
+    println!("Unique ratio: {ratio:.3} ({unique} unique / {total} total)");
+    if ratio < min {
+        runtime_error(&format!(
+            "unique word ratio {ratio:.3} is below the --min-unique-ratio threshold {min}"
+        ));
+    }
+}
+
+// Loi de Zipf: le mot de rang r apparaît environ (compte du rang 1) / r fois
+fn print_zipf(items: &[(String, u64)], width: Option<usize>, graphemes: bool) {
+    let Some(&(_, top_count)) = items.first() else {
+        println!("Zipf fit: no words");
+        return;
+    };
+
+    println!("Zipf fit (predicted = top count / rank):");
+    println!("rank  word  observed  predicted  ratio");
+
+    let mut abs_err_sum = 0.0;
+    for (i, (word, count)) in items.iter().enumerate() {
+        let rank = i + 1;
+        let predicted = top_count as f64 / rank as f64;
+        let ratio = *count as f64 / predicted;
+        abs_err_sum += (ratio - 1.0).abs();
+        let word = clip_word(word, width, graphemes);
+        println!("{rank}  {word}  {count}  {predicted:.2}  {ratio:.3}");
+    }
+
+    let mean_err = abs_err_sum / items.len() as f64;
+    println!("Mean absolute ratio error: {mean_err:.3}");
+}
+
+fn parse_sort(raw: &str) -> SortMode {
+    match raw {
+        "freq" => SortMode::Freq,
+        "alpha" => SortMode::Alpha,
+        "alpha-desc" => SortMode::AlphaDesc,
+        _ => usage_error(&format!(
+            "unknown sort mode '{raw}' (expected freq, alpha or alpha-desc)"
+        )),
+    }
+}
+
+// Avec alpha/alpha-desc, --top garde les N premiers mots dans l'ordre alphabétique
+fn sort_items(items: &mut [(String, u64)], mode: SortMode) {
+    match mode {
+        SortMode::Freq => items.sort_by(|(wa, ca), (wb, cb)| cb.cmp(ca).then_with(|| wa.cmp(wb))),
+        SortMode::Alpha => items.sort_by(|(wa, _), (wb, _)| wa.cmp(wb)),
+        SortMode::AlphaDesc => items.sort_by(|(wa, _), (wb, _)| wb.cmp(wa)),
+    }
+}
+
+fn parse_encoding(raw: &str) -> &'static Encoding {
+    // Alias courants sans tiret, en plus des labels WHATWG
+    let label = match raw.to_ascii_lowercase().as_str() {
+        "utf16le" => "utf-16le".to_string(),
+        "utf16be" => "utf-16be".to_string(),
+        "utf8" => "utf-8".to_string(),
+        other => other.to_string(),
+    };
+    Encoding::for_label(label.as_bytes())
+        .unwrap_or_else(|| usage_error(&format!("unknown encoding '{raw}'")))
+}
+
+// --progress: une ligne sur stderr réécrite sur place, au plus une fois par intervalle
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+const READ_CHUNK: usize = 1 << 20;
+// Mots comptés entre deux consultations de l'horloge
+const PROGRESS_EVERY_WORDS: u64 = 4096;
+
+struct Progress {
+    last: Option<Instant>,
+    shown: bool,
+}
+
+impl Progress {
+    fn new() -> Self {
+        Progress {
+            last: None,
+            shown: false,
+        }
+    }
+
+    fn due(&mut self, now: Instant) -> bool {
+        let due = self
+            .last
+            .is_none_or(|last| now.duration_since(last) >= PROGRESS_INTERVAL);
+        if due {
+            self.last = Some(now);
+        }
+        due
+    }
+
+    fn update(&mut self, msg: impl FnOnce() -> String) {
+        if self.due(Instant::now()) {
+            eprint!("\r{}", msg());
+            self.shown = true;
+        }
+    }
+
+    // Dernier état, puis fin de ligne pour ne pas coller le message suivant
+    fn finish(self, msg: impl FnOnce() -> String) {
+        if self.shown {
+            eprintln!("\r{}", msg());
+        }
+    }
+}
+
+fn read_status(bytes: usize) -> String {
+    format!("read {:.1} MiB", bytes as f64 / (1 << 20) as f64)
+}
+
+// Comme read_to_end, par blocs de READ_CHUNK pour pouvoir signaler l'avancement
+fn read_all(mut reader: impl Read, progress: bool) -> io::Result<Vec<u8>> {
     let mut bytes = Vec::new();
-    io::stdin()
-        .read_to_end(&mut bytes)
+    let mut chunk = vec![0u8; READ_CHUNK];
+    let mut meter = progress.then(Progress::new);
+    loop {
+        let n = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        bytes.extend_from_slice(&chunk[..n]);
+        if let Some(m) = meter.as_mut() {
+            m.update(|| read_status(bytes.len()));
+        }
+    }
+    if let Some(m) = meter {
+        m.finish(|| read_status(bytes.len()));
+    }
+    Ok(bytes)
+}
+
+fn read_stdin_lossy(encoding: &'static Encoding, progress: bool) -> String {
+    let bytes = read_all(io::stdin(), progress)
         .unwrap_or_else(|e| runtime_error(&format!("failed to read stdin: {e}")));
-    String::from_utf8_lossy(&bytes).into_owned()
+    let (text, _, _) = encoding.decode(&bytes);
+    text.into_owned()
+}
+
+fn read_file_lossy(path: &PathBuf, encoding: &'static Encoding) -> String {
+    read_file_with_progress(path, encoding, false)
+}
+
+fn read_file_with_progress(path: &PathBuf, encoding: &'static Encoding, progress: bool) -> String {
+    let bytes = std::fs::File::open(path)
+        .and_then(|f| read_all(f, progress))
+        .unwrap_or_else(|e| runtime_error(&format!("failed to read '{}': {e}", path.display())));
+    let (text, _, _) = encoding.decode(&bytes);
+    text.into_owned()
 }
 
 fn parse_args() -> Config {
@@ -65,6 +680,35 @@ fn parse_args() -> Config {
     let mut min_length: usize = 1;
     let mut ignore_case = false;
     let mut top_was_set = false;
+    let mut prefix: Option<String> = None;
+    let mut suffix: Option<String> = None;
+    let mut markdown = false;
+    let mut jsonl = false;
+    let mut progress = false;
+    let mut dedup = false;
+    let mut head_tail: Option<usize> = None;
+    let mut top_per_length: Option<usize> = None;
+    let mut zipf = false;
+    let mut encoding = UTF_8;
+    let mut input_file: Option<PathBuf> = None;
+    let mut force_stdin = false;
+    let mut bucket_numbers = false;
+    let mut buckets: Vec<(String, Regex)> = Vec::new();
+    let mut diff: Option<PathBuf> = None;
+    let mut percent = false;
+    let mut digest = false;
+    let mut entropy = false;
+    let mut min_unique_ratio: Option<f64> = None;
+    let mut only_file: Option<PathBuf> = None;
+    let mut replace_file: Option<PathBuf> = None;
+    let mut show_zero = false;
+    let mut trim_mode = false;
+    let mut grapheme_length = false;
+    let mut squash: Option<usize> = None;
+    let mut max_word_width: Option<usize> = None;
+    let mut stem_words = false;
+    let mut sort = SortMode::Freq;
+    let mut collocations = false;
 
     let mut positionals: Vec<String> = Vec::new();
     let mut it = env::args().skip(1).peekable();
@@ -78,6 +722,137 @@ fn parse_args() -> Config {
             "--ignore-case" => {
                 ignore_case = true;
             }
+            "--markdown" => {
+                markdown = true;
+            }
+            "--jsonl" => {
+                jsonl = true;
+            }
+            "--progress" => {
+                progress = true;
+            }
+            "--dedup-lines" => {
+                dedup = true;
+            }
+            _ if arg.starts_with("--head-tail=") => {
+                let raw = &arg["--head-tail=".len()..];
+                head_tail = Some(parse_usize_opt("--head-tail", raw));
+            }
+            "--head-tail" => {
+                let raw = it
+                    .next()
+                    .unwrap_or_else(|| usage_error("--head-tail requires a value"));
+                head_tail = Some(parse_usize_opt("--head-tail", &raw));
+            }
+            _ if arg.starts_with("--top-per-length=") => {
+                let raw = &arg["--top-per-length=".len()..];
+                top_per_length = Some(parse_usize_opt("--top-per-length", raw));
+            }
+            "--top-per-length" => {
+                let raw = it
+                    .next()
+                    .unwrap_or_else(|| usage_error("--top-per-length requires a value"));
+                top_per_length = Some(parse_usize_opt("--top-per-length", &raw));
+            }
+            "--zipf" => {
+                zipf = true;
+            }
+            "--collocations" => {
+                collocations = true;
+            }
+            _ if arg.starts_with("--sort=") => {
+                sort = parse_sort(&arg["--sort=".len()..]);
+            }
+            "--sort" => {
+                let raw = it
+                    .next()
+                    .unwrap_or_else(|| usage_error("--sort requires a value"));
+                sort = parse_sort(&raw);
+            }
+            "--percent" => {
+                percent = true;
+            }
+            "--digest" => {
+                digest = true;
+            }
+            "--entropy" => {
+                entropy = true;
+            }
+            _ if arg.starts_with("--min-unique-ratio=") => {
+                let raw = &arg["--min-unique-ratio=".len()..];
+                min_unique_ratio = Some(parse_ratio_opt("--min-unique-ratio", raw));
+            }
+            "--min-unique-ratio" => {
+                let raw = it
+                    .next()
+                    .unwrap_or_else(|| usage_error("--min-unique-ratio requires a value"));
+                min_unique_ratio = Some(parse_ratio_opt("--min-unique-ratio", &raw));
+            }
+            "--show-zero" => {
+                show_zero = true;
+            }
+            "--trim-mode" => {
+                trim_mode = true;
+            }
+            "--grapheme-length" => {
+                grapheme_length = true;
+            }
+            "--stem" => {
+                stem_words = true;
+            }
+            _ if arg.starts_with("--squash-repeats=") => {
+                let raw = &arg["--squash-repeats=".len()..];
+                squash = Some(parse_usize_opt("--squash-repeats", raw));
+            }
+            "--squash-repeats" => {
+                let raw = it
+                    .next()
+                    .unwrap_or_else(|| usage_error("--squash-repeats requires a value"));
+                squash = Some(parse_usize_opt("--squash-repeats", &raw));
+            }
+            _ if arg.starts_with("--max-word-width=") => {
+                let raw = &arg["--max-word-width=".len()..];
+                max_word_width = Some(parse_usize_opt("--max-word-width", raw));
+            }
+            "--max-word-width" => {
+                let raw = it
+                    .next()
+                    .unwrap_or_else(|| usage_error("--max-word-width requires a value"));
+                max_word_width = Some(parse_usize_opt("--max-word-width", &raw));
+            }
+            _ if arg.starts_with("--replace=") => {
+                replace_file = Some(PathBuf::from(&arg["--replace=".len()..]));
+            }
+            "--replace" => {
+                let raw = it
+                    .next()
+                    .unwrap_or_else(|| usage_error("--replace requires a value"));
+                replace_file = Some(PathBuf::from(raw));
+            }
+            _ if arg.starts_with("--only=") => {
+                only_file = Some(PathBuf::from(&arg["--only=".len()..]));
+            }
+            "--only" => {
+                let raw = it
+                    .next()
+                    .unwrap_or_else(|| usage_error("--only requires a value"));
+                only_file = Some(PathBuf::from(raw));
+            }
+            "--stdin" => {
+                force_stdin = true;
+            }
+            "--bucket-numbers" => {
+                bucket_numbers = true;
+            }
+            _ if arg.starts_with("--bucket-by=") => {
+                buckets.push(parse_bucket(&arg["--bucket-by=".len()..]));
+            }
+            "--bucket-by" => {
+                let raw = it
+                    .next()
+                    .unwrap_or_else(|| usage_error("--bucket-by requires a value"));
+                buckets.push(parse_bucket(&raw));
+            }
             "--" => {
                 positionals.extend(it.by_ref());
                 break;
@@ -104,6 +879,51 @@ fn parse_args() -> Config {
                     .unwrap_or_else(|| usage_error("--min-length requires a value"));
                 min_length = parse_usize_opt("--min-length", &raw);
             }
+            _ if arg.starts_with("--prefix=") => {
+                prefix = Some(arg["--prefix=".len()..].to_string());
+            }
+            "--prefix" => {
+                let raw = it
+                    .next()
+                    .unwrap_or_else(|| usage_error("--prefix requires a value"));
+                prefix = Some(raw);
+            }
+            _ if arg.starts_with("--suffix=") => {
+                suffix = Some(arg["--suffix=".len()..].to_string());
+            }
+            "--suffix" => {
+                let raw = it
+                    .next()
+                    .unwrap_or_else(|| usage_error("--suffix requires a value"));
+                suffix = Some(raw);
+            }
+            _ if arg.starts_with("--diff=") => {
+                diff = Some(PathBuf::from(&arg["--diff=".len()..]));
+            }
+            "--diff" => {
+                let raw = it
+                    .next()
+                    .unwrap_or_else(|| usage_error("--diff requires a value"));
+                diff = Some(PathBuf::from(raw));
+            }
+            _ if arg.starts_with("--input=") => {
+                input_file = Some(PathBuf::from(&arg["--input=".len()..]));
+            }
+            "--input" => {
+                let raw = it
+                    .next()
+                    .unwrap_or_else(|| usage_error("--input requires a value"));
+                input_file = Some(PathBuf::from(raw));
+            }
+            _ if arg.starts_with("--encoding=") => {
+                encoding = parse_encoding(&arg["--encoding=".len()..]);
+            }
+            "--encoding" => {
+                let raw = it
+                    .next()
+                    .unwrap_or_else(|| usage_error("--encoding requires a value"));
+                encoding = parse_encoding(&raw);
+            }
             _ if arg.starts_with('-') => {
                 usage_error(&format!("unknown option '{arg}' (try --help)"));
             }
@@ -111,52 +931,762 @@ fn parse_args() -> Config {
         }
     }
 
-    let input_text = if positionals.is_empty() {
-        None
+    // Une ligne = un objet: ni en-tête, ni pied de page, mots non tronqués
+    if jsonl
+        && (markdown
+            || zipf
+            || diff.is_some()
+            || collocations
+            || percent
+            || digest
+            || entropy
+            || min_unique_ratio.is_some()
+            || max_word_width.is_some())
+    {
+        usage_error(
+            "--jsonl cannot be combined with --markdown, --zipf, --diff, --collocations, --percent, --digest, --entropy, --min-unique-ratio or --max-word-width",
+        );
+    }
+    if markdown && zipf {
+        usage_error("--markdown cannot be combined with --zipf");
+    }
+    if diff.is_some() && (markdown || zipf) {
+        usage_error("--diff cannot be combined with --markdown or --zipf");
+    }
+    if percent && (zipf || diff.is_some()) {
+        usage_error("--percent cannot be combined with --zipf or --diff");
+    }
+    if digest && (zipf || diff.is_some()) {
+        usage_error("--digest cannot be combined with --zipf or --diff");
+    }
+
+    if entropy && (zipf || diff.is_some()) {
+        usage_error("--entropy cannot be combined with --zipf or --diff");
+    }
+    if min_unique_ratio.is_some() && (zipf || diff.is_some() || collocations) {
+        usage_error("--min-unique-ratio cannot be combined with --zipf, --diff or --collocations");
+    }
+    if collocations
+        && (markdown || zipf || diff.is_some() || percent || digest || entropy || show_zero)
+    {
+        usage_error(
+            "--collocations cannot be combined with --markdown, --zipf, --diff, --percent, --digest, --entropy or --show-zero",
+        );
+    }
+    if collocations && sort != SortMode::Freq {
+        usage_error("--collocations is always ranked by score (drop --sort)");
+    }
+
+    // Les deux extrémités de la distribution: remplace --top, tri par fréquence seulement
+    if head_tail.is_some() {
+        if head_tail == Some(0) {
+            usage_error("--head-tail must be at least 1");
+        }
+        if top_was_set || markdown || jsonl || zipf || diff.is_some() || collocations || digest {
+            usage_error(
+                "--head-tail cannot be combined with --top, --markdown, --jsonl, --zipf, --diff, --collocations or --digest",
+            );
+        }
+        if sort != SortMode::Freq {
+            usage_error("--head-tail requires --sort freq");
+        }
+    }
+
+    // Classement groupé par longueur: remplace --top et les autres listes
+    if top_per_length.is_some() {
+        if top_per_length == Some(0) {
+            usage_error("--top-per-length must be at least 1");
+        }
+        if top_was_set
+            || head_tail.is_some()
+            || markdown
+            || jsonl
+            || zipf
+            || diff.is_some()
+            || collocations
+            || digest
+        {
+            usage_error(
+                "--top-per-length cannot be combined with --top, --head-tail, --markdown, --jsonl, --zipf, --diff, --collocations or --digest",
+            );
+        }
+    }
+
+    // Zipf compare les rangs par fréquence: un tri alphabétique n'a pas de sens
+    if zipf && sort != SortMode::Freq {
+        usage_error("--zipf requires --sort freq");
+    }
+
+    if squash == Some(0) {
+        usage_error("--squash-repeats must be at least 1");
+    }
+    if max_word_width == Some(0) {
+        usage_error("--max-word-width must be at least 1");
+    }
+    if show_zero && only_file.is_none() {
+        usage_error("--show-zero requires --only");
+    }
+
+    // --input l'emporte sur --stdin, comme documenté
+    let input = if let Some(path) = input_file {
+        Input::File(path)
+    } else if force_stdin || positionals.is_empty() {
+        Input::Stdin
     } else {
-        Some(positionals.join(" "))
+        Input::Text(positionals.join(" "))
     };
 
+    // Avec --stem, la liste blanche est comparée aux radicaux
+    let only = only_file.as_ref().map(|path| {
+        let allowed = read_allowlist(path, encoding, ignore_case);
+        if stem_words {
+            allowed.iter().map(|w| stem(w)).collect()
+        } else {
+            allowed
+        }
+    });
+
+    let replace = replace_file
+        .as_ref()
+        .map(|path| read_replacements(path, encoding, ignore_case));
+
     Config {
         top,
         min_length,
         ignore_case,
         top_was_set,
-        input_text,
+        prefix,
+        suffix,
+        markdown,
+        jsonl,
+        progress,
+        dedup_lines: dedup,
+        head_tail,
+        top_per_length,
+        zipf,
+        encoding,
+        bucket_numbers,
+        buckets,
+        diff,
+        percent,
+        digest,
+        entropy,
+        min_unique_ratio,
+        only,
+        replace,
+        show_zero,
+        trim_mode,
+        squash_repeats: squash,
+        max_word_width,
+        grapheme_length,
+        stem: stem_words,
+        sort,
+        collocations,
+        input,
     }
 }
 
+fn count_words(text: &str, cfg: &Config) -> HashMap<String, u64> {
+    let mut freq: HashMap<String, u64> = HashMap::new();
+    let mut meter = cfg.progress.then(Progress::new);
+    let mut seen = 0u64;
+    for_each_word(text, cfg, |w| {
+        *freq.entry(w.to_string()).or_insert(0) += 1;
+        seen += 1;
+        if let Some(m) = meter.as_mut()
+            && seen.is_multiple_of(PROGRESS_EVERY_WORDS)
+        {
+            m.update(|| format!("counted {} words", format_with_commas(seen)));
+        }
+    });
+    if let Some(m) = meter {
+        m.finish(|| format!("counted {} words", format_with_commas(seen)));
+    }
+
+    if cfg.show_zero
+        && let Some(allowed) = &cfg.only
+    {
+        for w in allowed {
+            freq.entry(w.clone()).or_insert(0);
+        }
+    }
+
+    freq
+}
+
+// Flux ordonné des mots comptés (après normalisation, filtres et buckets)
+fn for_each_word(text: &str, cfg: &Config, mut f: impl FnMut(&str)) {
+    // Les filtres sont comparés au texte déjà replié si --ignore-case
+    let (text, prefix, suffix) = if cfg.ignore_case {
+        (
+            text.to_lowercase(),
+            cfg.prefix.as_deref().map(str::to_lowercase),
+            cfg.suffix.as_deref().map(str::to_lowercase),
+        )
+    } else {
+        (text.to_string(), cfg.prefix.clone(), cfg.suffix.clone())
+    };
+
+    let tokens: Box<dyn Iterator<Item = &str>> = match (cfg.trim_mode, cfg.grapheme_length) {
+        (true, false) => Box::new(text.split_whitespace().map(trim_token)),
+        (true, true) => Box::new(text.split_whitespace().map(trim_token_graphemes)),
+        (false, false) => Box::new(text.split(|c: char| !is_word_char(c))),
+        (false, true) => Box::new(grapheme_tokens(&text)),
+    };
+    let word_len = if cfg.grapheme_length {
+        grapheme_core_len
+    } else {
+        core_len
+    };
+
+    // Après repli de casse: "SOOO" et "sooo" donnent le même token
+    tokens
+        .map(|w| squash_repeats(w, cfg.squash_repeats))
+        .map(
+            |w| match cfg.replace.as_ref().and_then(|m| m.get(w.as_ref())) {
+                Some(to) => Cow::Owned(to.clone()),
+                None => w,
+            },
+        )
+        .map(|w| if cfg.stem { Cow::Owned(stem(&w)) } else { w })
+        .filter(|w| !w.is_empty())
+        .filter(|w| word_len(w) >= cfg.min_length)
+        .filter(|w| prefix.as_deref().is_none_or(|p| w.starts_with(p)))
+        .filter(|w| suffix.as_deref().is_none_or(|s| w.ends_with(s)))
+        .filter(|w| {
+            cfg.only
+                .as_ref()
+                .is_none_or(|allowed| allowed.contains(w.as_ref()))
+        })
+        .for_each(|w| f(bucket_for(&w, cfg)));
+}
+
+// Paires vues une seule fois écartées: la PMI les surévalue systématiquement
+const MIN_PAIR_COUNT: u64 = 2;
+
+// Paires de mots voisins classées par PMI: log2(n(a b) * N / (n(a) * n(b)))
+fn collocations(text: &str, cfg: &Config) -> Vec<(String, String, u64, f64)> {
+    let mut unigrams: HashMap<String, u64> = HashMap::new();
+    let mut pairs: HashMap<(String, String), u64> = HashMap::new();
+    let mut prev: Option<String> = None;
+    for_each_word(text, cfg, |w| {
+        *unigrams.entry(w.to_string()).or_insert(0) += 1;
+        if let Some(p) = prev.replace(w.to_string()) {
+            *pairs.entry((p, w.to_string())).or_insert(0) += 1;
+        }
+    });
+
+    let n = unigrams.values().sum::<u64>() as f64;
+    let mut ranked: Vec<(String, String, u64, f64)> = pairs
+        .into_iter()
+        .filter(|&(_, c)| c >= MIN_PAIR_COUNT)
+        .map(|((a, b), c)| {
+            let expected = unigrams[&a] as f64 * unigrams[&b] as f64 / n;
+            let score = (c as f64 / expected).log2();
+            (a, b, c, score)
+        })
+        .collect();
+    ranked.sort_by(|x, y| {
+        y.3.total_cmp(&x.3)
+            .then_with(|| y.2.cmp(&x.2))
+            .then_with(|| (&x.0, &x.1).cmp(&(&y.0, &y.1)))
+    });
+    ranked
+}
+
+// Mots dont la fréquence change le plus entre l'entrée principale (a) et --diff (b)
+fn print_diff(
+    a: &HashMap<String, u64>,
+    b: &HashMap<String, u64>,
+    top: usize,
+    width: Option<usize>,
+    graphemes: bool,
+) {
+    let mut deltas: Vec<(&str, u64, u64, i64)> = a
+        .keys()
+        .chain(b.keys().filter(|w| !a.contains_key(*w)))
+        .map(|w| {
+            let ca = a.get(w).copied().unwrap_or(0);
+            let cb = b.get(w).copied().unwrap_or(0);
+            (w.as_str(), ca, cb, cb as i64 - ca as i64)
+        })
+        .filter(|&(_, _, _, d)| d != 0)
+        .collect();
+    deltas.sort_by(|x, y| {
+        y.3.unsigned_abs()
+            .cmp(&x.3.unsigned_abs())
+            .then_with(|| x.0.cmp(y.0))
+    });
+
+    println!("Frequency changes:");
+    for (word, ca, cb, d) in deltas.into_iter().take(top) {
+        let word = clip_word(word, width, graphemes);
+        println!("{word}: {ca} -> {cb} ({d:+})");
+    }
+}
+
+// Première occurrence de chaque ligne (comparaison exacte, fin de ligne exclue) et nb de lignes retirées
+fn dedup_lines(text: &str) -> (String, usize) {
+    let mut seen = HashSet::new();
+    let mut out = String::with_capacity(text.len());
+    let mut dropped = 0;
+    for line in text.lines() {
+        if seen.insert(line) {
+            out.push_str(line);
+            out.push('\n');
+        } else {
+            dropped += 1;
+        }
+    }
+    (out, dropped)
+}
+
+// Sur stderr: la sortie (jsonl, digest...) reste intacte
+fn apply_dedup(text: String, cfg: &Config, what: &str) -> String {
+    if !cfg.dedup_lines {
+        return text;
+    }
+    let (text, dropped) = dedup_lines(&text);
+    eprintln!("Dropped {dropped} duplicate lines from {what}");
+    text
+}
+
 fn main() {
     let cfg = parse_args();
 
-    let mut text = match cfg.input_text {
-        Some(t) => t,
-        None => read_stdin_lossy(),
+    let text = match &cfg.input {
+        Input::File(path) => read_file_with_progress(path, cfg.encoding, cfg.progress),
+        Input::Stdin => read_stdin_lossy(cfg.encoding, cfg.progress),
+        Input::Text(t) => t.clone(),
     };
+    let text = apply_dedup(text, &cfg, "input");
 
-    if cfg.ignore_case {
-        text = text.to_lowercase();
+    if cfg.collocations {
+        let ranked = collocations(&text, &cfg);
+        println!("Top {} collocations:", cfg.top);
+        for (a, b, count, score) in ranked.into_iter().take(cfg.top) {
+            let (a, b) = (
+                clip_word(&a, cfg.max_word_width, cfg.grapheme_length),
+                clip_word(&b, cfg.max_word_width, cfg.grapheme_length),
+            );
+            println!("{a} {b}: {count} (PMI {score:.2})");
+        }
+        return;
     }
 
-    let mut freq: HashMap<String, u64> = HashMap::new();
+    let freq = count_words(&text, &cfg);
 
-    text.split(|c: char| !is_word_char(c))
-        .filter(|w| !w.is_empty())
-        .filter(|w| core_len(w) >= cfg.min_length)
-        .for_each(|w| {
-            *freq.entry(w.to_string()).or_insert(0) += 1;
-        });
+    if let Some(path) = &cfg.diff {
+        let other = read_file_with_progress(path, cfg.encoding, cfg.progress);
+        let other = count_words(&apply_dedup(other, &cfg, "--diff file"), &cfg);
+        print_diff(
+            &freq,
+            &other,
+            cfg.top,
+            cfg.max_word_width,
+            cfg.grapheme_length,
+        );
+        return;
+    }
+
+    // Total calculé avant troncature: les pourcentages portent sur tous les mots comptés
+    let total = cfg.percent.then(|| freq.values().sum::<u64>());
+    // Idem pour l'entropie: distribution complète, pas seulement le top N
+    let entropy = cfg.entropy.then(|| entropy_bits(freq.values().copied()));
+    let uniqueness = cfg.min_unique_ratio.map(|min| (unique_ratio(&freq), min));
 
     let mut items: Vec<(String, u64)> = freq.into_iter().collect();
-    items.sort_by(|(wa, ca), (wb, cb)| cb.cmp(ca).then_with(|| wa.cmp(wb)));
+    sort_items(&mut items, cfg.sort);
 
-    if cfg.top_was_set {
+    if let Some(k) = cfg.top_per_length {
+        for (i, (len, words)) in top_per_length(items, k, cfg.grapheme_length)
+            .into_iter()
+            .enumerate()
+        {
+            if i > 0 {
+                println!();
+            }
+            println!("Length {len}:");
+            for (word, count) in words {
+                let word = clip_word(&word, cfg.max_word_width, cfg.grapheme_length);
+                match total {
+                    Some(total) => {
+                        println!("  {word}: {count} ({:.1}%)", percent_of(count, total))
+                    }
+                    None => println!("  {word}: {count}"),
+                }
+            }
+        }
+        if let Some(h) = entropy {
+            println!("\nEntropy: {h:.2} bits");
+        }
+        if let Some((stats, min)) = uniqueness {
+            println!();
+            check_unique_ratio(stats, min);
+        }
+        return;
+    }
+
+    let distinct = items.len();
+    let mut tail = None;
+    if let Some(n) = cfg.head_tail {
+        (items, tail) = split_head_tail(items, n);
+    } else {
+        items.truncate(cfg.top);
+    }
+
+    let digest = cfg.digest.then(|| digest_items(&items));
+
+    if cfg.jsonl {
+        print_jsonl(&items);
+        return;
+    }
+    if cfg.markdown {
+        print_markdown(&items, total, cfg.max_word_width, cfg.grapheme_length);
+        if let Some(d) = digest {
+            println!("\nDigest: {d:016x}");
+        }
+        if let Some(h) = entropy {
+            println!("\nEntropy: {h:.2} bits");
+        }
+        if let Some((stats, min)) = uniqueness {
+            println!();
+            check_unique_ratio(stats, min);
+        }
+        return;
+    }
+    if cfg.zipf {
+        print_zipf(&items, cfg.max_word_width, cfg.grapheme_length);
+        return;
+    }
+
+    if let Some(n) = cfg.head_tail {
+        if tail.is_some() {
+            println!("Top {n} and bottom {n} words:");
+        } else {
+            println!("Only {distinct} distinct words (fewer than 2 x {n}), showing all:");
+        }
+    } else if cfg.top_was_set {
         println!("Top {} words:", cfg.top);
     } else {
         println!("Word frequency:");
     }
 
-    for (word, count) in items.into_iter().take(cfg.top) {
-        println!("{word}: {count}");
+    let print_item = |word: &str, count: u64| {
+        let word = clip_word(word, cfg.max_word_width, cfg.grapheme_length);
+        match total {
+            Some(total) => println!("{word}: {count} ({:.1}%)", percent_of(count, total)),
+            None => println!("{word}: {count}"),
+        }
+    };
+    for (word, count) in items {
+        print_item(&word, count);
+    }
+    if let Some(tail) = tail {
+        println!("...");
+        for (word, count) in tail {
+            print_item(&word, count);
+        }
+    }
+
+    if let Some(d) = digest {
+        println!("Digest: {d:016x}");
+    }
+    if let Some(h) = entropy {
+        println!("Entropy: {h:.2} bits");
+    }
+    if let Some((stats, min)) = uniqueness {
+        check_unique_ratio(stats, min);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        Config {
+            top: 10,
+            min_length: 1,
+            ignore_case: false,
+            top_was_set: false,
+            prefix: None,
+            suffix: None,
+            markdown: false,
+            jsonl: false,
+            progress: false,
+            dedup_lines: false,
+            head_tail: None,
+            top_per_length: None,
+            zipf: false,
+            encoding: UTF_8,
+            bucket_numbers: false,
+            buckets: Vec::new(),
+            diff: None,
+            percent: false,
+            digest: false,
+            entropy: false,
+            min_unique_ratio: None,
+            only: None,
+            replace: None,
+            show_zero: false,
+            trim_mode: false,
+            squash_repeats: None,
+            max_word_width: None,
+            grapheme_length: false,
+            stem: false,
+            sort: SortMode::Freq,
+            collocations: false,
+            input: Input::Stdin,
+        }
+    }
+
+    #[test]
+    fn clip_word_counts_chars_not_bytes() {
+        assert_eq!(
+            clip_word("supercalifragilistic", Some(14), false),
+            "supercalifrag…"
+        );
+        assert_eq!(clip_word("short", Some(5), false), "short");
+        assert_eq!(clip_word("élévation", Some(4), false), "élé…");
+        assert_eq!(clip_word("anything", None, false), "anything");
+    }
+
+    #[test]
+    fn grapheme_length_counts_emoji_and_combining_marks_as_one() {
+        // "cafe" + accent aigu combinant, drapeau (2 indicateurs régionaux),
+        // famille ZWJ (5 caractères)
+        let text = "cafe\u{301} \u{1F1EB}\u{1F1F7}\u{1F1EB}\u{1F1F7} \u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467} ok";
+        let mut cfg = config();
+        cfg.min_length = 2;
+
+        // Par caractères: l'accent coupe "café", les emoji sont des séparateurs
+        let chars = count_words(text, &cfg);
+        assert_eq!(chars.get("cafe"), Some(&1));
+        assert_eq!(chars.len(), 2);
+
+        cfg.grapheme_length = true;
+        let graphemes = count_words(text, &cfg);
+        assert_eq!(graphemes.get("cafe\u{301}"), Some(&1));
+        assert_eq!(
+            graphemes.get("\u{1F1EB}\u{1F1F7}\u{1F1EB}\u{1F1F7}"),
+            Some(&1)
+        );
+        assert_eq!(graphemes.get("ok"), Some(&1));
+        // La famille est une seule grappe: trop courte pour --min-length 2
+        assert_eq!(graphemes.len(), 3);
+
+        cfg.trim_mode = true;
+        let trimmed = count_words("(cafe\u{301}!) \u{1F44D}\u{1F44D}.", &cfg);
+        assert_eq!(trimmed.get("cafe\u{301}"), Some(&1));
+        assert_eq!(trimmed.get("\u{1F44D}\u{1F44D}"), Some(&1));
+
+        assert_eq!(
+            clip_word("\u{1F1EB}\u{1F1F7}\u{1F1EF}\u{1F1F5}abc", Some(3), true),
+            "\u{1F1EB}\u{1F1F7}\u{1F1EF}\u{1F1F5}…"
+        );
+        assert_eq!(grapheme_core_len("cafe\u{301}"), 4);
+    }
+
+    #[test]
+    fn unique_ratio_ignores_zero_counts() {
+        let freq: HashMap<String, u64> = [("spam", 3), ("ham", 1), ("unused", 0)]
+            .into_iter()
+            .map(|(w, c)| (w.to_string(), c))
+            .collect();
+        assert_eq!(unique_ratio(&freq), (2, 4, 0.5));
+        assert_eq!(unique_ratio(&HashMap::new()), (0, 0, 0.0));
+    }
+
+    #[test]
+    fn json_escape_handles_quotes_and_controls() {
+        assert_eq!(json_escape("plain"), "plain");
+        assert_eq!(json_escape("say \"hi\""), "say \\\"hi\\\"");
+        assert_eq!(json_escape("a\\b"), "a\\\\b");
+        assert_eq!(json_escape("tab\there\u{1}"), "tab\\there\\u0001");
+        assert_eq!(json_escape("naïve"), "naïve");
+    }
+
+    #[test]
+    fn top_per_length_ranks_within_each_bucket() {
+        let mut cfg = config();
+        cfg.ignore_case = true;
+        cfg.min_length = 3;
+        let freq = count_words(
+            "The cat and the dog. A crane, a crate, the CAT; tree tree",
+            &cfg,
+        );
+        let mut items: Vec<(String, u64)> = freq.into_iter().collect();
+        sort_items(&mut items, SortMode::Freq);
+        let groups = top_per_length(items, 2, false);
+        // "a" est sous --min-length; égalités départagées par ordre alphabétique
+        assert_eq!(groups.keys().copied().collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert_eq!(
+            groups[&3],
+            vec![("the".to_string(), 3), ("cat".to_string(), 2)]
+        );
+        assert_eq!(groups[&4], vec![("tree".to_string(), 2)]);
+        assert_eq!(
+            groups[&5],
+            vec![("crane".to_string(), 1), ("crate".to_string(), 1)]
+        );
+
+        // Avec --grapheme-length, un drapeau (deux scalaires) compte pour 1
+        let items = vec![("🇫🇷".to_string(), 2), ("a".to_string(), 1)];
+        let groups = top_per_length(items, 2, true);
+        assert_eq!(groups.keys().copied().collect::<Vec<_>>(), vec![1]);
+        assert_eq!(groups[&1].len(), 2);
+    }
+
+    #[test]
+    fn head_tail_never_repeats_items() {
+        let items: Vec<u32> = (1..=7).collect();
+        assert_eq!(
+            split_head_tail(items.clone(), 2),
+            (vec![1, 2], Some(vec![6, 7]))
+        );
+        assert_eq!(
+            split_head_tail(items.clone(), 3),
+            (vec![1, 2, 3], Some(vec![5, 6, 7]))
+        );
+        assert_eq!(split_head_tail(items.clone(), 4), (items, None));
+    }
+
+    #[test]
+    fn dedup_lines_keeps_first_occurrences() {
+        let log = "GET /a ok\nGET /b ok\nGET /a ok\r\nGET /a ok \nGET /b ok\n";
+        let (text, dropped) = dedup_lines(log);
+        assert_eq!(text, "GET /a ok\nGET /b ok\nGET /a ok \n");
+        assert_eq!(dropped, 2);
+
+        let freq = count_words(&text, &config());
+        assert_eq!(freq.get("GET"), Some(&3));
+        assert_eq!(dedup_lines(""), (String::new(), 0));
+    }
+
+    #[test]
+    fn progress_updates_are_rate_limited() {
+        let mut p = Progress::new();
+        let t0 = Instant::now();
+        assert!(p.due(t0));
+        assert!(!p.due(t0 + PROGRESS_INTERVAL / 2));
+        assert!(p.due(t0 + PROGRESS_INTERVAL));
+        assert!(!p.due(t0 + PROGRESS_INTERVAL + Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn squash_repeats_merges_elongations() {
+        assert_eq!(squash_repeats("soooo", Some(2)), "soo");
+        assert_eq!(squash_repeats("sooo", Some(2)), "soo");
+        assert_eq!(squash_repeats("soo", Some(2)), "soo");
+        assert_eq!(squash_repeats("looool", Some(1)), "lol");
+        assert!(matches!(squash_repeats("book", Some(2)), Cow::Borrowed(_)));
+        assert_eq!(squash_repeats("soooo", None), "soooo");
+
+        let cfg = Config {
+            ignore_case: true,
+            squash_repeats: Some(2),
+            ..config()
+        };
+        let freq = count_words("so sooo SOOOOO Sooooooo soo lol", &cfg);
+        assert_eq!(freq.get("soo"), Some(&4));
+        assert_eq!(freq.get("so"), Some(&1));
+        assert_eq!(freq.len(), 3);
+    }
+
+    #[test]
+    fn stem_strips_common_suffixes() {
+        assert_eq!(stem("runs"), "run");
+        assert_eq!(stem("running"), "run");
+        assert_eq!(stem("stopped"), "stop");
+        assert_eq!(stem("played"), "play");
+        assert_eq!(stem("falling"), "fall");
+        assert_eq!(stem("boxes"), "box");
+        assert_eq!(stem("watches"), "watch");
+        assert_eq!(stem("makes"), "make");
+
+        // Mots laissés intacts: radical trop court, -ss, pas de voyelle
+        assert_eq!(stem("sing"), "sing");
+        assert_eq!(stem("need"), "need");
+        assert_eq!(stem("bus"), "bus");
+        assert_eq!(stem("glass"), "glass");
+        assert_eq!(stem("run"), "run");
+
+        let cfg = Config {
+            ignore_case: true,
+            stem: true,
+            ..config()
+        };
+        let freq = count_words("Run runs running ran", &cfg);
+        assert_eq!(freq.get("run"), Some(&3));
+        assert_eq!(freq.get("ran"), Some(&1));
+    }
+
+    #[test]
+    fn sort_modes_order_items() {
+        let items = vec![
+            ("pear".to_string(), 1),
+            ("apple".to_string(), 2),
+            ("fig".to_string(), 2),
+            ("kiwi".to_string(), 5),
+        ];
+        let words = |mode| {
+            let mut v = items.clone();
+            sort_items(&mut v, mode);
+            v.into_iter().map(|(w, _)| w).collect::<Vec<_>>()
+        };
+        assert_eq!(words(SortMode::Freq), ["kiwi", "apple", "fig", "pear"]);
+        assert_eq!(words(SortMode::Alpha), ["apple", "fig", "kiwi", "pear"]);
+        assert_eq!(words(SortMode::AlphaDesc), ["pear", "kiwi", "fig", "apple"]);
+    }
+
+    #[test]
+    fn collocations_prefer_exclusive_pairs() {
+        let cfg = Config {
+            ignore_case: true,
+            ..config()
+        };
+        // "new york" toujours ensemble; "of the" fréquent mais "the" est partout
+        let text = "New York of the city. New York of the state. \
+                    the the the of the river, the lake, the New York";
+        let ranked = collocations(text, &cfg);
+        let pairs: Vec<(&str, &str)> = ranked
+            .iter()
+            .map(|(a, b, _, _)| (a.as_str(), b.as_str()))
+            .collect();
+        assert_eq!(pairs[0], ("new", "york"));
+        assert_eq!(ranked[0].2, 3);
+        let of_the = pairs.iter().position(|&p| p == ("of", "the")).unwrap();
+        assert!(ranked[of_the].3 < ranked[0].3);
+        // Paires vues une seule fois ignorées
+        assert!(!pairs.contains(&("the", "river")));
+    }
+
+    #[test]
+    fn replace_map_folds_variants() {
+        let map: HashMap<String, String> = [("u", "you"), ("ya", "you"), ("favour", "favor")]
+            .into_iter()
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .collect();
+        let cfg = Config {
+            ignore_case: true,
+            replace: Some(map),
+            ..config()
+        };
+        let freq = count_words("U and ya and you; a favour, a FAVOR", &cfg);
+        assert_eq!(freq.get("you"), Some(&3));
+        assert_eq!(freq.get("favor"), Some(&2));
+        assert_eq!(freq.get("and"), Some(&2));
+        assert!(!freq.contains_key("u") && !freq.contains_key("favour"));
+    }
+
+    #[test]
+    fn entropy_of_simple_distributions() {
+        assert_eq!(entropy_bits([7].into_iter()), 0.0);
+        assert_eq!(entropy_bits([].into_iter()), 0.0);
+        assert!((entropy_bits([3, 3, 3, 3].into_iter()) - 2.0).abs() < 1e-12);
+        // Un mot dominant: bien en dessous du maximum log2(4) = 2
+        let skewed = entropy_bits([97, 1, 1, 1].into_iter());
+        assert!(skewed > 0.0 && skewed < 0.3, "{skewed}");
     }
 }
@@ -1,7 +1,7 @@
 use clap::Parser;
 use std::fs::OpenOptions;
 use std::io::{Read, Seek, SeekFrom, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -19,18 +19,152 @@ struct Cli {
     #[arg(short = 'r', long = "read", conflicts_with = "write")]
     read: bool,
 
-    /// Write mode (hex string to write)
+    /// Write mode (hex string to write, repeatable)
     #[arg(
         short = 'w',
         long = "write",
         value_name = "HEX",
         conflicts_with = "read"
     )]
-    write: Option<String>,
+    write: Vec<String>,
 
-    /// Offset in bytes (decimal or 0x hex)
-    #[arg(short = 'o', long = "offset", value_name = "OFFSET", value_parser = parse_u64_dec_or_hex)]
-    offset: Option<u64>,
+    /// Offset in bytes (decimal or 0x hex, end-N from EOF, +N after the previous write; repeatable)
+    #[arg(short = 'o', long = "offset", value_name = "OFFSET", value_parser = parse_offset)]
+    offset: Vec<Offset>,
+
+    /// Write mode: hex pattern written --repeat times at --offset
+    #[arg(long = "pattern", value_name = "HEX", conflicts_with_all = ["read", "write"])]
+    pattern: Option<String>,
+
+    /// Number of times to write --pattern
+    #[arg(
+        long = "repeat",
+        value_name = "N",
+        requires = "pattern",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    repeat: u64,
+
+    /// Copy LEN bytes from SRC to DST inside the file; overlapping ranges are handled
+    #[arg(
+        long = "move",
+        value_name = "SRC:DST:LEN",
+        conflicts_with_all = ["read", "write", "pattern", "offset"],
+        value_parser = parse_move
+    )]
+    move_range: Option<MoveSpec>,
+
+    /// Swap the high and low nibble of each byte in --offset/--size, in place (0xAB -> 0xBA)
+    #[arg(long = "swap-nibbles", conflicts_with_all = ["read", "write", "pattern"])]
+    swap_nibbles: bool,
+
+    /// Compare bytes at --offset against this hex string and suggest a patch
+    #[arg(long = "compare-hex", value_name = "HEX", conflicts_with_all = ["read", "write"])]
+    compare_hex: Option<String>,
+
+    /// Show what a write would do without touching the file
+    #[arg(
+        long = "dry-run",
+        conflicts_with_all = ["read", "compare_hex", "checksum_file", "info", "detect"]
+    )]
+    dry_run: bool,
+
+    /// Prefix each written payload with its 4-byte big-endian length
+    #[arg(long = "frame", conflicts_with = "read")]
+    frame: bool,
+
+    /// Read a 4-byte big-endian length at --offset and dump that many following bytes
+    #[arg(long = "unframe", requires = "read", conflicts_with = "size")]
+    unframe: bool,
+
+    /// Pad the hex column of a short final line so the ASCII panel stays aligned (read mode)
+    #[arg(long = "align", requires = "read", conflicts_with = "read_int")]
+    align: bool,
+
+    /// Print offsets with at least N hex digits [default: enough for the file size, min 8]
+    #[arg(
+        long = "offset-digits",
+        value_name = "N",
+        value_parser = clap::value_parser!(u8).range(1..=16)
+    )]
+    offset_digits: Option<u8>,
+
+    /// Collapse repeated identical lines into a single '*' (read mode)
+    #[arg(long = "squeeze", requires = "read")]
+    squeeze: bool,
+
+    /// Dump every `OFFSET SIZE` range listed in FILE (one per line, decimal or 0x hex)
+    #[arg(
+        long = "ranges",
+        value_name = "FILE",
+        requires = "read",
+        conflicts_with_all = ["offset", "size", "unframe", "count", "every", "read_int"]
+    )]
+    ranges: Option<PathBuf>,
+
+    /// Also copy the raw bytes of the read range to PATH while dumping them
+    #[arg(
+        long = "also-write",
+        value_name = "PATH",
+        requires = "read",
+        conflicts_with_all = ["ranges", "follow", "unframe", "count", "every", "read_int"]
+    )]
+    also_write: Option<PathBuf>,
+
+    /// After EOF, keep polling and dump bytes appended to the file until interrupted
+    #[arg(
+        long = "follow",
+        requires = "read",
+        conflicts_with_all = ["size", "unframe", "squeeze", "count", "every", "read_int", "ranges"]
+    )]
+    follow: bool,
+
+    /// Count occurrences of one byte value in the read range instead of dumping it
+    #[arg(
+        long = "count",
+        value_name = "HEX_BYTE",
+        requires = "read",
+        conflicts_with_all = ["unframe", "squeeze"],
+        value_parser = parse_hex_byte
+    )]
+    count: Option<u8>,
+
+    /// Dump only every Nth byte of the range (offsets are those of the sampled bytes)
+    #[arg(
+        long = "every",
+        value_name = "N",
+        requires = "read",
+        conflicts_with_all = ["unframe", "squeeze", "count"],
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    every: Option<u64>,
+
+    /// Decode one integer at --offset (u8, i16le, u32be, i64le, ...)
+    #[arg(
+        long = "read-int",
+        value_name = "TYPE",
+        requires = "read",
+        conflicts_with_all = ["unframe", "squeeze", "count", "every", "size"],
+        value_parser = parse_int_type
+    )]
+    read_int: Option<IntType>,
+
+    /// Check the CRC-32 of the range (--offset/--size, default whole file) against the hex value in PATH
+    #[arg(long = "checksum-file", value_name = "PATH")]
+    checksum_file: Option<PathBuf>,
+
+    /// Print file metadata (path, size, first bytes) instead of dumping it
+    #[arg(long = "info", conflicts_with_all = ["offset", "size"])]
+    info: bool,
+
+    /// Guess the file type from its magic bytes (alone, or as an extra --info field)
+    #[arg(long = "detect", conflicts_with_all = ["offset", "size"])]
+    detect: bool,
+
+    /// With --info, print the metadata as a single JSON object
+    #[arg(long = "json", requires = "info")]
+    json: bool,
 
     /// Number of bytes to read
     #[arg(short = 's', long = "size", value_name = "SIZE", value_parser = parse_u64_dec_or_hex)]
@@ -47,9 +181,43 @@ fn print_help() {
     println!("Options:");
     println!("-f, --file   Target file");
     println!("-r, --read   Read mode (display hex)");
-    println!("-w, --write  Write mode (hex string to write)");
-    println!("-o, --offset Offset in bytes (decimal or 0x hex)");
+    println!("-w, --write  Write mode (hex string to write, repeatable; '#' comments allowed)");
+    println!("-o, --offset Offset in bytes (decimal or 0x hex, paired with each --write)");
+    println!("             end-N = N bytes before EOF, +N = N bytes after the previous write");
     println!("-s, --size   Number of bytes to read");
+    println!("    --pattern HEX --repeat N  Write HEX repeated N times at --offset");
+    println!(
+        "    --compare-hex HEX  Diff bytes at --offset against HEX and print a fixing --write"
+    );
+    println!("    --move SRC:DST:LEN  Copy LEN bytes from SRC to DST (overlap-safe, like memmove)");
+    println!(
+        "    --swap-nibbles  Swap the two nibbles of each byte in --offset/--size (0xAB -> 0xBA)"
+    );
+    println!(
+        "    --dry-run  Show what --write/--move/--swap-nibbles would do without modifying the file"
+    );
+    println!("    --frame    Prefix written bytes with a 4-byte big-endian length");
+    println!("    --unframe  Read a 4-byte big-endian length at --offset, then that many bytes");
+    println!(
+        "    --align    Pad a short last line so the |ascii| column lines up (like hexdump -C)"
+    );
+    println!(
+        "    --offset-digits N  Minimum hex digits of offsets (read/compare; default: sized from the file, min 8)"
+    );
+    println!("    --squeeze  Replace repeated identical lines with '*' (read mode)");
+    println!("    --ranges FILE  Dump each 'OFFSET SIZE' line of FILE (read mode, '#' comments)");
+    println!("    --also-write PATH  Tee the raw bytes of the read range to PATH (read mode)");
+    println!("    --follow   Keep dumping bytes appended after EOF, like tail -f (read mode)");
+    println!("    --count HEX_BYTE  Count a byte value in the read range (first/last offsets)");
+    println!("    --read-int TYPE  Decode one integer at --offset (u8/i8, u16..i64 + le/be)");
+    println!("    --every N  Sample every Nth byte: each line holds 16 sampled bytes, not");
+    println!("               16 consecutive ones; the offset is that of the first sample");
+    println!("    --checksum-file PATH  Compare the CRC-32 of --offset/--size (default: whole");
+    println!("               file) to the hex value in PATH; exit 0 on match, 1 otherwise");
+    println!("    --info     Print path, size and the first 16 bytes of the file");
+    println!("    --detect   Guess the file type from magic bytes (PNG, ELF, ZIP, ...); adds a");
+    println!("               type field to --info");
+    println!("    --json     With --info, print the same fields as one JSON object");
     println!("-h, --help   Print help");
 }
 
@@ -70,6 +238,132 @@ fn parse_u64_dec_or_hex(raw: &str) -> Result<u64, String> {
     }
 }
 
+// Offset tel que saisi: absolu, depuis la fin (end-N) ou relatif à la position courante (+N)
+#[derive(Clone, Copy, Debug)]
+enum Offset {
+    Start(u64),
+    End(u64),
+    Current(u64),
+}
+
+fn parse_offset(raw: &str) -> Result<Offset, String> {
+    let s = raw.trim();
+    if let Some(n) = s.strip_prefix("end-") {
+        parse_u64_dec_or_hex(n).map(Offset::End)
+    } else if s == "end" {
+        Ok(Offset::End(0))
+    } else if let Some(n) = s.strip_prefix('+') {
+        parse_u64_dec_or_hex(n).map(Offset::Current)
+    } else {
+        parse_u64_dec_or_hex(s).map(Offset::Start)
+    }
+}
+
+// `len` = taille du fichier, `cursor` = position après l'opération précédente
+fn resolve_offset(offset: Offset, len: u64, cursor: u64) -> u64 {
+    match offset {
+        Offset::Start(n) => n,
+        Offset::End(n) => len.checked_sub(n).unwrap_or_else(|| {
+            die(&format!(
+                "invalid offset end-{n} (file is only {len} bytes long)"
+            ))
+        }),
+        Offset::Current(n) => cursor
+            .checked_add(n)
+            .unwrap_or_else(|| die("invalid offset (overflow)")),
+    }
+}
+
+// Taille actuelle du fichier, 0 s'il n'existe pas encore (mode écriture)
+fn file_len_or_zero(path: &PathBuf) -> u64 {
+    match std::fs::metadata(path) {
+        Ok(m) => m.len(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+        Err(e) => die(&format!("failed to stat file '{:?}': {e}", path)),
+    }
+}
+
+// Type entier pour --read-int: signe, largeur en octets, boutisme
+#[derive(Clone, Debug)]
+struct IntType {
+    name: String,
+    signed: bool,
+    width: usize,
+    little: bool,
+}
+
+fn parse_int_type(raw: &str) -> Result<IntType, String> {
+    let name = raw.trim().to_ascii_lowercase();
+    let invalid =
+        || format!("invalid integer type '{raw}' (expected e.g. u8, i16le, u32be, i64le)");
+    let (signed, rest) = match name.as_bytes().first() {
+        Some(b'u') => (false, &name[1..]),
+        Some(b'i') => (true, &name[1..]),
+        _ => return Err(invalid()),
+    };
+    let (bits, little) = if let Some(b) = rest.strip_suffix("le") {
+        (b, true)
+    } else if let Some(b) = rest.strip_suffix("be") {
+        (b, false)
+    } else {
+        (rest, true)
+    };
+    let width = match bits {
+        "8" => 1,
+        "16" => 2,
+        "32" => 4,
+        "64" => 8,
+        _ => return Err(invalid()),
+    };
+    if width > 1 && bits == rest {
+        return Err(format!(
+            "integer type '{raw}' needs an endianness suffix (le or be)"
+        ));
+    }
+    Ok(IntType {
+        name,
+        signed,
+        width,
+        little,
+    })
+}
+
+// --move SRC:DST:LEN, chaque champ en décimal ou 0x hex
+#[derive(Clone, Copy, Debug)]
+struct MoveSpec {
+    src: u64,
+    dst: u64,
+    len: u64,
+}
+
+fn parse_move(raw: &str) -> Result<MoveSpec, String> {
+    let parts: Vec<&str> = raw.split(':').collect();
+    let [src, dst, len] = parts[..] else {
+        return Err(format!(
+            "invalid move '{raw}' (expected SRC:DST:LEN, e.g. 0x10:0x20:8)"
+        ));
+    };
+    Ok(MoveSpec {
+        src: parse_u64_dec_or_hex(src)?,
+        dst: parse_u64_dec_or_hex(dst)?,
+        len: parse_u64_dec_or_hex(len)?,
+    })
+}
+
+fn parse_hex_byte(raw: &str) -> Result<u8, String> {
+    let s = raw.trim();
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    if hex.is_empty() || hex.len() > 2 {
+        return Err(format!(
+            "invalid byte '{raw}' (expected 1-2 hex digits, e.g. 00 or 0xff)"
+        ));
+    }
+    u8::from_str_radix(hex, 16).map_err(|_| format!("invalid byte '{raw}' (expected hex)"))
+}
+
 fn is_printable_ascii(b: u8) -> bool {
     (0x20..=0x7e).contains(&b)
 }
@@ -99,7 +393,14 @@ fn bytes_to_ascii(bytes: &[u8]) -> String {
 }
 
 fn parse_hex_string_to_bytes(input: &str) -> Result<Vec<u8>, String> {
-    let trimmed = input.trim();
+    // Hex annoté: "# ..." jusqu'à la fin de ligne et '\' de continuation en fin de ligne sont ignorés
+    let stripped: Vec<&str> = input
+        .lines()
+        .map(|line| line.split_once('#').map_or(line, |(hex, _)| hex))
+        .map(|line| line.trim_end().strip_suffix('\\').unwrap_or(line))
+        .collect();
+    let stripped = stripped.join("\n");
+    let trimmed = stripped.trim();
     let no_prefix = trimmed
         .strip_prefix("0x")
         .or_else(|| trimmed.strip_prefix("0X"))
@@ -151,24 +452,343 @@ fn main() {
     let file_path = cli
         .file
         .unwrap_or_else(|| die("--file is required (try --help)"));
-    let offset = cli.offset.unwrap_or(0);
 
     let mode_read = cli.read;
-    let mode_write = cli.write.is_some();
+    let mode_write = !cli.write.is_empty() || cli.pattern.is_some();
+    let mode_compare = cli.compare_hex.is_some();
+    let mode_move = cli.move_range.is_some();
+    let mode_verify = cli.checksum_file.is_some();
 
-    if mode_read == mode_write {
-        die("choose exactly one mode: --read or --write (try --help)");
+    if [
+        mode_read,
+        mode_write,
+        mode_compare,
+        cli.info,
+        cli.detect && !cli.info,
+        mode_move,
+        mode_verify,
+        cli.swap_nibbles,
+    ]
+    .iter()
+    .filter(|&&m| m)
+    .count()
+        != 1
+    {
+        die(
+            "choose exactly one mode: --read, --write/--pattern, --move, --swap-nibbles, --compare-hex, --checksum-file, --detect or --info (try --help)",
+        );
+    }
+    if cli.offset_digits.is_some() && !mode_read && !mode_compare {
+        die("--offset-digits applies to --read and --compare-hex only");
     }
 
-    if mode_read {
-        run_read(&file_path, offset, cli.size);
+    if cli.info {
+        run_info(&file_path, cli.json, cli.detect);
+    } else if cli.detect {
+        match detect(&read_prefix(&file_path, DETECT_LEN)) {
+            Some(kind) => println!("detected: {kind}"),
+            None => println!("unknown"),
+        }
+    } else if let Some(mv) = cli.move_range {
+        run_move(&file_path, mv, cli.dry_run);
+    } else if cli.swap_nibbles {
+        if cli.offset.len() > 1 {
+            die("--swap-nibbles accepts a single --offset");
+        }
+        let offset = single_offset(&file_path, cli.offset.first().copied());
+        run_swap_nibbles(&file_path, offset, cli.size, cli.dry_run);
+    } else if let Some(hex) = cli.compare_hex.as_deref() {
+        if cli.offset.len() > 1 {
+            die("--compare-hex accepts a single --offset");
+        }
+        let offset = single_offset(&file_path, cli.offset.first().copied());
+        let digits = file_offset_digits(&file_path, cli.offset_digits);
+        let identical = run_compare(&file_path, offset, hex, digits);
+        std::process::exit(if identical { 0 } else { 1 });
+    } else if let Some(sidecar) = cli.checksum_file.as_deref() {
+        if cli.offset.len() > 1 {
+            die("--checksum-file accepts a single --offset");
+        }
+        let offset = single_offset(&file_path, cli.offset.first().copied());
+        let matches = run_verify_checksum(&file_path, offset, cli.size, sidecar);
+        std::process::exit(if matches { 0 } else { 1 });
+    } else if mode_read {
+        if cli.offset.len() > 1 {
+            die("--read accepts a single --offset");
+        }
+        let offset = single_offset(&file_path, cli.offset.first().copied());
+        let file_len = std::fs::metadata(&file_path).map(|m| m.len()).unwrap_or(0);
+        let opts = ReadOpts {
+            squeeze: cli.squeeze,
+            align: cli.align,
+            offset_digits: file_offset_digits(&file_path, cli.offset_digits),
+        };
+        if let Some(list) = cli.ranges.as_deref() {
+            run_read_ranges(&file_path, list, file_len, &opts);
+        } else if cli.follow {
+            run_follow(&file_path, offset, &opts);
+        } else if let Some(ty) = &cli.read_int {
+            run_read_int(&file_path, offset, ty);
+        } else if let Some(needle) = cli.count {
+            run_count(&file_path, offset, cli.size, needle, opts.offset_digits);
+        } else if let Some(step) = cli.every {
+            run_read_every(&file_path, offset, cli.size, step, &opts);
+        } else if cli.unframe {
+            run_unframe(&file_path, offset, &opts);
+        } else {
+            let mut tee = cli.also_write.as_deref().map(open_tee);
+            run_read(&file_path, offset, cli.size, &opts, tee.as_mut());
+            if let (Some(tee), Some(sink)) = (tee, cli.also_write.as_deref())
+                && !tee.finish(sink)
+            {
+                std::process::exit(1);
+            }
+        }
     } else {
-        let hex = cli.write.expect("write mode guaranteed");
-        run_write(&file_path, offset, &hex);
+        let payloads: Vec<Vec<u8>> = match cli.pattern.as_deref() {
+            Some(hex) => vec![pattern_payload(hex, cli.repeat, cli.frame)],
+            None => cli
+                .write
+                .iter()
+                .map(|hex| write_payload(hex, cli.frame))
+                .collect(),
+        };
+        let offsets = pair_write_offsets(payloads.len(), &cli.offset);
+        // En dry-run, la taille projetée est propagée d'une écriture à l'autre
+        let mut projected_len = None;
+        let mut cursor = 0u64;
+        for (bytes, spec) in payloads.iter().zip(offsets) {
+            let len = projected_len.unwrap_or_else(|| file_len_or_zero(&file_path));
+            let offset = resolve_offset(spec, len, cursor);
+            let end = offset
+                .checked_add(bytes.len() as u64)
+                .unwrap_or_else(|| die("offset + length overflows"));
+            if cli.dry_run {
+                projected_len = Some(dry_run_write(offset, bytes, len));
+            } else {
+                run_write(&file_path, offset, bytes);
+            }
+            cursor = end;
+        }
     }
 }
 
-fn run_read(path: &PathBuf, offset: u64, size: Option<u64>) {
+fn write_payload(hex: &str, frame: bool) -> Vec<u8> {
+    let payload =
+        parse_hex_string_to_bytes(hex).unwrap_or_else(|e| die(&format!("invalid hex: {e}")));
+    frame_payload(payload, frame)
+}
+
+// Le motif est construit en mémoire avant l'écriture: au-delà, refus plutôt qu'un abort
+const MAX_PATTERN_BYTES: usize = 256 * 1024 * 1024;
+
+// Motif répété: DEAD x4 -> DE AD DE AD DE AD DE AD
+fn pattern_payload(hex: &str, repeat: u64, frame: bool) -> Vec<u8> {
+    let pattern =
+        parse_hex_string_to_bytes(hex).unwrap_or_else(|e| die(&format!("invalid hex: {e}")));
+    let total = usize::try_from(repeat)
+        .ok()
+        .and_then(|times| times.checked_mul(pattern.len()))
+        .filter(|&total| total <= MAX_PATTERN_BYTES)
+        .unwrap_or_else(|| {
+            die(&format!(
+                "--pattern x --repeat is too large (max {MAX_PATTERN_BYTES} bytes)"
+            ))
+        });
+    let times = repeat as usize;
+    println!(
+        "Pattern: {} bytes x {repeat} = {total} bytes",
+        pattern.len()
+    );
+    frame_payload(pattern.repeat(times), frame)
+}
+
+// Même format de trame que streamchat: longueur u32 big-endian puis payload
+fn frame_payload(payload: Vec<u8>, frame: bool) -> Vec<u8> {
+    if !frame {
+        return payload;
+    }
+    let len: u32 = payload
+        .len()
+        .try_into()
+        .unwrap_or_else(|_| die("payload too large for a 4-byte length frame"));
+    let mut out = Vec::with_capacity(payload.len() + 4);
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(&payload);
+    out
+}
+
+// Modes à une seule opération: la position courante est le début du fichier
+fn single_offset(path: &PathBuf, offset: Option<Offset>) -> u64 {
+    match offset {
+        None => 0,
+        Some(Offset::End(n)) => resolve_offset(Offset::End(n), file_len_or_zero(path), 0),
+        Some(o) => resolve_offset(o, 0, 0),
+    }
+}
+
+// La Nième --write est associée au Nième --offset (une seule écriture peut omettre l'offset)
+fn pair_write_offsets(writes: usize, offsets: &[Offset]) -> Vec<Offset> {
+    if writes == 1 && offsets.len() <= 1 {
+        return vec![offsets.first().copied().unwrap_or(Offset::Start(0))];
+    }
+    if offsets.len() != writes {
+        die(&format!(
+            "got {writes} --write but {} --offset (each --write needs its own --offset)",
+            offsets.len()
+        ));
+    }
+    offsets.to_vec()
+}
+
+// --also-write: copie brute à côté du dump. Une erreur d'écriture coupe la copie
+// (signalée une fois sur stderr) sans interrompre l'affichage
+struct Tee<W: Write> {
+    sink: Option<W>,
+    written: u64,
+    error: Option<std::io::Error>,
+}
+
+impl<W: Write> Tee<W> {
+    fn new(sink: W) -> Self {
+        Self {
+            sink: Some(sink),
+            written: 0,
+            error: None,
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let Some(sink) = self.sink.as_mut() else {
+            return;
+        };
+        match sink.write_all(bytes) {
+            Ok(()) => self.written += bytes.len() as u64,
+            Err(e) => {
+                eprintln!("warning: --also-write failed, continuing the dump only: {e}");
+                self.error = Some(e);
+                self.sink = None;
+            }
+        }
+    }
+
+    // Vide le tampon; renvoie le nombre d'octets arrivés dans la copie
+    fn close(mut self) -> Result<u64, std::io::Error> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        if let Some(sink) = self.sink.as_mut() {
+            sink.flush()?;
+        }
+        Ok(self.written)
+    }
+}
+
+type FileTee = Tee<std::io::BufWriter<std::fs::File>>;
+
+fn open_tee(path: &Path) -> FileTee {
+    let file = std::fs::File::create(path)
+        .unwrap_or_else(|e| die(&format!("failed to create '{:?}': {e}", path)));
+    Tee::new(std::io::BufWriter::new(file))
+}
+
+impl FileTee {
+    // false si la copie est incomplète: le dump, lui, est allé jusqu'au bout
+    fn finish(self, path: &Path) -> bool {
+        match self.close() {
+            Ok(n) => {
+                eprintln!("Teed {n} bytes to {}", path.display());
+                true
+            }
+            Err(e) => {
+                eprintln!("error: --also-write to {} failed: {e}", path.display());
+                false
+            }
+        }
+    }
+}
+
+// Options d'affichage du mode lecture
+#[derive(Debug, Default)]
+struct ReadOpts {
+    squeeze: bool,
+    align: bool,
+    offset_digits: usize,
+}
+
+// Chiffres hexadécimaux nécessaires pour le plus grand offset affiché (la taille), 8 au minimum
+fn offset_digits_for(len: u64) -> usize {
+    ((64 - len.leading_zeros()).div_ceil(4) as usize).max(8)
+}
+
+// Largeur calculée une fois pour tout le fichier: toutes les lignes restent alignées
+fn file_offset_digits(path: &PathBuf, requested: Option<u8>) -> usize {
+    offset_digits_for(file_len_or_zero(path)).max(requested.unwrap_or(0) as usize)
+}
+
+// 16 octets par ligne: "xx " x 16 sans l'espace final
+const HEX_COLUMN_WIDTH: usize = 16 * 3 - 1;
+
+fn print_dump_line(offset: u64, bytes: &[u8], opts: &ReadOpts) {
+    let hex_part = bytes_to_spaced_hex(bytes);
+    let ascii_part = bytes_to_ascii(bytes);
+    let digits = opts.offset_digits;
+    if opts.align {
+        println!("{offset:0digits$x}: {hex_part:<HEX_COLUMN_WIDTH$} |{ascii_part}|");
+    } else {
+        println!("{offset:0digits$x}: {hex_part} |{ascii_part}|");
+    }
+}
+
+// Lignes "offset taille" (décimal ou 0x), '#' pour commenter; renvoie (ligne, offset, taille)
+fn parse_ranges(content: &str) -> Result<Vec<(usize, u64, u64)>, String> {
+    let mut ranges = Vec::new();
+    for (i, raw) in content.lines().enumerate() {
+        let line = raw.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let [off, size] = fields[..] else {
+            return Err(format!(
+                "line {}: expected 'OFFSET SIZE', got '{}'",
+                i + 1,
+                raw.trim()
+            ));
+        };
+        let off = parse_u64_dec_or_hex(off).map_err(|e| format!("line {}: {e}", i + 1))?;
+        let size = parse_u64_dec_or_hex(size).map_err(|e| format!("line {}: {e}", i + 1))?;
+        ranges.push((i + 1, off, size));
+    }
+    Ok(ranges)
+}
+
+fn run_read_ranges(path: &PathBuf, list: &Path, file_len: u64, opts: &ReadOpts) {
+    let content = std::fs::read_to_string(list)
+        .unwrap_or_else(|e| die(&format!("failed to read ranges file '{:?}': {e}", list)));
+    let ranges =
+        parse_ranges(&content).unwrap_or_else(|e| die(&format!("ranges file '{:?}': {e}", list)));
+
+    // Tout est vérifié avant le premier dump: pas de sortie partielle sur une plage invalide
+    if let Some(&(line, off, _)) = ranges.iter().find(|&&(_, off, _)| off > file_len) {
+        die(&format!(
+            "ranges file '{:?}': line {line}: offset 0x{off:x} is past end of file",
+            list
+        ));
+    }
+    for (_, off, size) in ranges {
+        println!("--- range @ 0x{off:x} len {size} ---");
+        run_read(path, off, Some(size), opts, None);
+    }
+}
+
+fn run_read(
+    path: &PathBuf,
+    offset: u64,
+    size: Option<u64>,
+    opts: &ReadOpts,
+    mut tee: Option<&mut FileTee>,
+) {
     let mut file = std::fs::File::open(path).unwrap_or_else(|e| {
         die(&format!("failed to open file '{:?}': {e}", path));
     });
@@ -190,6 +810,8 @@ fn run_read(path: &PathBuf, offset: u64, size: Option<u64>) {
 
     let mut remaining = to_read;
     let mut base_off = offset;
+    let mut prev: Option<Vec<u8>> = None;
+    let mut squeezing = false;
 
     while remaining > 0 {
         let chunk_len = remaining.min(16) as usize;
@@ -210,20 +832,546 @@ fn run_read(path: &PathBuf, offset: u64, size: Option<u64>) {
         if buf.is_empty() {
             break;
         }
+        if let Some(tee) = tee.as_deref_mut() {
+            tee.write(&buf);
+        }
 
-        let hex_part = bytes_to_spaced_hex(&buf);
-        let ascii_part = bytes_to_ascii(&buf);
-        println!("{:08x}: {} |{}|", base_off, hex_part, ascii_part);
+        // Comme hexdump: une ligne identique à la précédente devient '*'
+        if opts.squeeze && prev.as_deref() == Some(buf.as_slice()) {
+            if !squeezing {
+                println!("*");
+                squeezing = true;
+            }
+        } else {
+            squeezing = false;
+            print_dump_line(base_off, &buf, opts);
+        }
 
         base_off += buf.len() as u64;
         remaining -= buf.len() as u64;
+        if opts.squeeze {
+            prev = Some(buf);
+        }
+    }
+
+    if opts.squeeze {
+        println!("{base_off:0width$x}", width = opts.offset_digits);
+    }
+}
+
+// --follow: attente entre deux sondages, et lecture maximale par sondage
+const FOLLOW_POLL: std::time::Duration = std::time::Duration::from_millis(200);
+const FOLLOW_CHUNK: u64 = 64 * 1024;
+
+// Résultat d'un sondage de --follow
+#[derive(Debug, PartialEq)]
+enum Polled {
+    Idle,
+    Truncated,
+    Appended(Vec<u8>),
+}
+
+// Octets ajoutés depuis `pos` (au plus `FOLLOW_CHUNK`), `pos` avançant d'autant;
+// `len` est la taille actuelle.
+// Un fichier devenu plus court que `pos` fait repartir de sa nouvelle fin.
+fn poll_appended<F: Read + Seek>(file: &mut F, pos: &mut u64, len: u64) -> std::io::Result<Polled> {
+    if len < *pos {
+        *pos = len;
+        return Ok(Polled::Truncated);
+    }
+    if len == *pos {
+        return Ok(Polled::Idle);
+    }
+    file.seek(SeekFrom::Start(*pos))?;
+    let mut buf = Vec::new();
+    file.take((len - *pos).min(FOLLOW_CHUNK))
+        .read_to_end(&mut buf)?;
+    if buf.is_empty() {
+        return Ok(Polled::Idle);
+    }
+    *pos += buf.len() as u64;
+    Ok(Polled::Appended(buf))
+}
+
+fn run_follow(path: &PathBuf, offset: u64, opts: &ReadOpts) {
+    let mut file = std::fs::File::open(path).unwrap_or_else(|e| {
+        die(&format!("failed to open file '{:?}': {e}", path));
+    });
+    let stat = |file: &std::fs::File| {
+        file.metadata()
+            .map(|m| m.len())
+            .unwrap_or_else(|e| die(&format!("failed to stat file '{:?}': {e}", path)))
+    };
+    if offset > stat(&file) {
+        die("invalid offset (past end of file)");
+    }
+
+    // Le premier sondage vide le fichier jusqu'à EOF, les suivants ne voient que l'ajout
+    let mut pos = offset;
+    loop {
+        let len = stat(&file);
+        let start = pos;
+        match poll_appended(&mut file, &mut pos, len)
+            .unwrap_or_else(|e| die(&format!("failed to read: {e}")))
+        {
+            Polled::Idle => std::thread::sleep(FOLLOW_POLL),
+            Polled::Truncated => {
+                eprintln!("File truncated to {len} bytes, following from there");
+            }
+            Polled::Appended(bytes) => {
+                for (i, line) in bytes.chunks(16).enumerate() {
+                    print_dump_line(start + (i * 16) as u64, line, opts);
+                }
+                // Sortie redirigée: sans flush, rien n'apparaîtrait avant la fin
+                std::io::stdout()
+                    .flush()
+                    .unwrap_or_else(|e| die(&format!("failed to write output: {e}")));
+            }
+        }
+    }
+}
+
+fn run_read_int(path: &PathBuf, offset: u64, ty: &IntType) {
+    let mut file = std::fs::File::open(path).unwrap_or_else(|e| {
+        die(&format!("failed to open file '{:?}': {e}", path));
+    });
+
+    let len = file
+        .metadata()
+        .map(|m| m.len())
+        .unwrap_or_else(|e| die(&format!("failed to stat file '{:?}': {e}", path)));
+
+    if offset.saturating_add(ty.width as u64) > len {
+        die(&format!(
+            "not enough bytes for {} at 0x{offset:x} (need {}, file is {len} bytes)",
+            ty.name, ty.width
+        ));
+    }
+
+    file.seek(SeekFrom::Start(offset))
+        .unwrap_or_else(|e| die(&format!("failed to seek: {e}")));
+    let mut raw = vec![0u8; ty.width];
+    file.read_exact(&mut raw)
+        .unwrap_or_else(|e| die(&format!("failed to read: {e}")));
+
+    // Valeur brute sur 64 bits, puis extension de signe si besoin
+    let mut be = raw.clone();
+    if ty.little {
+        be.reverse();
+    }
+    let bits = be.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+    let hex_digits = ty.width * 2;
+    let value = if ty.signed {
+        let shift = 64 - 8 * ty.width as u32;
+        (((bits << shift) as i64) >> shift).to_string()
+    } else {
+        bits.to_string()
+    };
+
+    println!(
+        "{} @ 0x{:x} = {} (0x{:0width$x}) [{}]",
+        ty.name,
+        offset,
+        value,
+        bits,
+        bytes_to_spaced_hex(&raw),
+        width = hex_digits
+    );
+}
+
+const INFO_PREVIEW_LEN: usize = 16;
+
+// Signatures connues: (position, octets, type). Les plus spécifiques d'abord,
+// la première qui correspond gagne; ajouter une ligne suffit pour un nouveau format.
+const MAGIC: &[(usize, &[u8], &str)] = &[
+    (0, b"\x89PNG\r\n\x1a\n", "PNG"),
+    (0, b"\xff\xd8\xff", "JPEG"),
+    (0, b"GIF87a", "GIF"),
+    (0, b"GIF89a", "GIF"),
+    (0, b"\x7fELF", "ELF"),
+    (0, b"%PDF-", "PDF"),
+    (0, b"PK\x03\x04", "ZIP"),
+    (0, b"PK\x05\x06", "ZIP"),
+    (0, b"\x1f\x8b", "GZIP"),
+    (0, b"BZh", "BZIP2"),
+    (0, b"\xfd7zXZ\x00", "XZ"),
+    (0, b"7z\xbc\xaf\x27\x1c", "7Z"),
+    (0, b"SQLite format 3\x00", "SQLite"),
+    (0, b"\x00asm", "WASM"),
+    (0, b"OggS", "OGG"),
+    (0, b"fLaC", "FLAC"),
+    (0, b"ID3", "MP3"),
+    (8, b"WAVE", "WAV"),
+    (8, b"WEBP", "WEBP"),
+    (4, b"ftyp", "MP4"),
+    (257, b"ustar", "TAR"),
+    (0, b"MZ", "PE/DOS executable"),
+];
+
+// Assez d'octets pour la signature la plus lointaine (tar, à 257)
+const DETECT_LEN: usize = 512;
+
+fn detect(prefix: &[u8]) -> Option<&'static str> {
+    MAGIC
+        .iter()
+        .find(|(at, magic, _)| prefix.get(*at..at + magic.len()) == Some(magic))
+        .map(|&(_, _, kind)| kind)
+}
+
+fn read_prefix(path: &PathBuf, len: usize) -> Vec<u8> {
+    let file = std::fs::File::open(path).unwrap_or_else(|e| {
+        die(&format!("failed to open file '{:?}': {e}", path));
+    });
+    let mut prefix = Vec::with_capacity(len);
+    file.take(len as u64)
+        .read_to_end(&mut prefix)
+        .unwrap_or_else(|e| die(&format!("failed to read: {e}")));
+    prefix
+}
+
+// Résumé du fichier; le format JSON est stable pour les scripts:
+// {"path": "...", "size": N, "preview": "xx xx ..."} (+ "type" avec --detect, null si inconnu)
+fn run_info(path: &PathBuf, json: bool, with_type: bool) {
+    let file = std::fs::File::open(path).unwrap_or_else(|e| {
+        die(&format!("failed to open file '{:?}': {e}", path));
+    });
+
+    let len = file
+        .metadata()
+        .map(|m| m.len())
+        .unwrap_or_else(|e| die(&format!("failed to stat file '{:?}': {e}", path)));
+
+    let mut head = Vec::with_capacity(DETECT_LEN);
+    file.take(DETECT_LEN as u64)
+        .read_to_end(&mut head)
+        .unwrap_or_else(|e| die(&format!("failed to read: {e}")));
+    let preview = &head[..head.len().min(INFO_PREVIEW_LEN)];
+    let kind = with_type.then(|| detect(&head));
+
+    let shown = path.display().to_string();
+    if json {
+        let type_field = match kind {
+            Some(Some(k)) => format!(", \"type\": {}", json_string(k)),
+            Some(None) => ", \"type\": null".to_string(),
+            None => String::new(),
+        };
+        println!(
+            "{{\"path\": {}, \"size\": {len}, \"preview\": \"{}\"{type_field}}}",
+            json_string(&shown),
+            bytes_to_spaced_hex(preview)
+        );
+    } else {
+        println!("File: {shown}");
+        println!("Size: {len} bytes (0x{len:x})");
+        println!(
+            "Preview: {} |{}|",
+            bytes_to_spaced_hex(preview),
+            bytes_to_ascii(preview)
+        );
+        if let Some(kind) = kind {
+            println!("Type: {}", kind.unwrap_or("unknown"));
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Échantillonnage: 16 octets prélevés (un tous les `step`) par ligne, positions réelles en tête
+fn run_read_every(path: &PathBuf, offset: u64, size: Option<u64>, step: u64, opts: &ReadOpts) {
+    let mut file = std::fs::File::open(path).unwrap_or_else(|e| {
+        die(&format!("failed to open file '{:?}': {e}", path));
+    });
+
+    let len = file
+        .metadata()
+        .map(|m| m.len())
+        .unwrap_or_else(|e| die(&format!("failed to stat file '{:?}': {e}", path)));
+
+    if offset > len {
+        die("invalid offset (past end of file)");
+    }
+
+    let available = len - offset;
+    let end = offset + size.unwrap_or(available).min(available);
+
+    file.seek(SeekFrom::Start(offset))
+        .unwrap_or_else(|e| die(&format!("failed to seek: {e}")));
+    // Tampon + saut relatif: pas d'appel système par octet échantillonné
+    let mut reader = std::io::BufReader::new(file);
+    let skip = i64::try_from(step - 1).unwrap_or(i64::MAX);
+
+    let mut line: Vec<u8> = Vec::with_capacity(16);
+    let mut line_start = offset;
+    let mut pos = offset;
+    let mut byte = [0u8; 1];
+
+    while pos < end {
+        reader
+            .read_exact(&mut byte)
+            .unwrap_or_else(|e| die(&format!("failed to read: {e}")));
+        if skip > 0 {
+            reader
+                .seek_relative(skip)
+                .unwrap_or_else(|e| die(&format!("failed to seek: {e}")));
+        }
+        if line.is_empty() {
+            line_start = pos;
+        }
+        line.push(byte[0]);
+        if line.len() == 16 {
+            print_dump_line(line_start, &line, opts);
+            line.clear();
+        }
+        pos = pos.saturating_add(step);
+    }
+    if !line.is_empty() {
+        print_dump_line(line_start, &line, opts);
+    }
+}
+
+// Parcourt la plage par blocs, sans charger le fichier en mémoire
+fn run_count(path: &PathBuf, offset: u64, size: Option<u64>, needle: u8, digits: usize) {
+    let mut file = std::fs::File::open(path).unwrap_or_else(|e| {
+        die(&format!("failed to open file '{:?}': {e}", path));
+    });
+
+    let len = file
+        .metadata()
+        .map(|m| m.len())
+        .unwrap_or_else(|e| die(&format!("failed to stat file '{:?}': {e}", path)));
+
+    if offset > len {
+        die("invalid offset (past end of file)");
+    }
+
+    let available = len - offset;
+    let to_read = size.unwrap_or(available).min(available);
+
+    file.seek(SeekFrom::Start(offset))
+        .unwrap_or_else(|e| die(&format!("failed to seek: {e}")));
+
+    let mut reader = file.take(to_read);
+    let mut buf = [0u8; 8192];
+    let mut pos = offset;
+    let mut count = 0u64;
+    let mut first = None;
+    let mut last = 0u64;
+
+    loop {
+        let n = reader
+            .read(&mut buf)
+            .unwrap_or_else(|e| die(&format!("failed to read: {e}")));
+        if n == 0 {
+            break;
+        }
+        for (i, &b) in buf[..n].iter().enumerate() {
+            if b == needle {
+                let at = pos + i as u64;
+                first.get_or_insert(at);
+                last = at;
+                count += 1;
+            }
+        }
+        pos += n as u64;
+    }
+
+    match first {
+        Some(first) => {
+            println!(
+                "0x{needle:02x} occurs {count} times (first 0x{first:0digits$x}, last 0x{last:0digits$x})"
+            )
+        }
+        None => println!("0x{needle:02x} not found"),
     }
 }
 
-fn run_write(path: &PathBuf, offset: u64, hex: &str) {
-    let bytes =
+// CRC-32 IEEE (zlib, cksum -a crc32b), table calculée à la compilation
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+// `crc` est l'état brut (initialisé à !0); le CRC final est `!crc`
+fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    bytes.iter().fold(crc, |c, &b| {
+        CRC32_TABLE[((c ^ b as u32) & 0xFF) as usize] ^ (c >> 8)
+    })
+}
+
+// Premier mot du fichier compagnon (format "crc  nom" accepté), 0x facultatif
+fn parse_sidecar_crc(content: &str) -> Result<u32, String> {
+    let token = content.split_whitespace().next().unwrap_or("");
+    let hex = token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+        .unwrap_or(token);
+    if hex.is_empty() || hex.len() > 8 {
+        return Err(format!(
+            "expected a CRC-32 as up to 8 hex digits, got '{token}'"
+        ));
+    }
+    u32::from_str_radix(hex, 16)
+        .map_err(|_| format!("expected a CRC-32 as up to 8 hex digits, got '{token}'"))
+}
+
+// Retourne true si le CRC-32 de la plage correspond à celui du fichier compagnon
+fn run_verify_checksum(path: &PathBuf, offset: u64, size: Option<u64>, sidecar: &Path) -> bool {
+    let content = std::fs::read_to_string(sidecar).unwrap_or_else(|e| {
+        die(&format!(
+            "failed to read checksum file '{:?}': {e}",
+            sidecar
+        ))
+    });
+    let expected = parse_sidecar_crc(&content)
+        .unwrap_or_else(|e| die(&format!("checksum file '{:?}': {e}", sidecar)));
+
+    let mut file = std::fs::File::open(path).unwrap_or_else(|e| {
+        die(&format!("failed to open file '{:?}': {e}", path));
+    });
+    let len = file
+        .metadata()
+        .map(|m| m.len())
+        .unwrap_or_else(|e| die(&format!("failed to stat file '{:?}': {e}", path)));
+    if offset > len {
+        die("invalid offset (past end of file)");
+    }
+    let to_read = size.unwrap_or(len - offset).min(len - offset);
+    file.seek(SeekFrom::Start(offset))
+        .unwrap_or_else(|e| die(&format!("failed to seek: {e}")));
+
+    let mut reader = file.take(to_read);
+    let mut buf = [0u8; 8192];
+    let mut crc = !0u32;
+    loop {
+        let n = reader
+            .read(&mut buf)
+            .unwrap_or_else(|e| die(&format!("failed to read: {e}")));
+        if n == 0 {
+            break;
+        }
+        crc = crc32_update(crc, &buf[..n]);
+    }
+    let computed = !crc;
+
+    if computed == expected {
+        println!("Checksum OK: crc32 {computed:08x} ({to_read} bytes at 0x{offset:08x})");
+        true
+    } else {
+        println!(
+            "Checksum mismatch: expected {expected:08x}, computed {computed:08x} ({to_read} bytes at 0x{offset:08x})"
+        );
+        false
+    }
+}
+
+// Retourne true si les octets du fichier correspondent exactement à `hex`
+fn run_compare(path: &PathBuf, offset: u64, hex: &str, digits: usize) -> bool {
+    let expected =
         parse_hex_string_to_bytes(hex).unwrap_or_else(|e| die(&format!("invalid hex: {e}")));
 
+    let mut file = std::fs::File::open(path).unwrap_or_else(|e| {
+        die(&format!("failed to open file '{:?}': {e}", path));
+    });
+    file.seek(SeekFrom::Start(offset))
+        .unwrap_or_else(|e| die(&format!("failed to seek: {e}")));
+
+    let mut actual = Vec::with_capacity(expected.len());
+    file.take(expected.len() as u64)
+        .read_to_end(&mut actual)
+        .unwrap_or_else(|e| die(&format!("failed to read: {e}")));
+
+    let mut first_diff = None;
+    let mut last_diff = 0usize;
+    for (i, &want) in expected.iter().enumerate() {
+        let pos = offset + i as u64;
+        match actual.get(i) {
+            Some(&got) if got == want => continue,
+            Some(&got) => println!("{pos:0digits$x}: expected {want:02x} got {got:02x}"),
+            None => println!("{pos:0digits$x}: expected {want:02x} got EOF"),
+        }
+        first_diff.get_or_insert(i);
+        last_diff = i;
+    }
+
+    let Some(first) = first_diff else {
+        println!(
+            "Bytes match ({} bytes at 0x{offset:0digits$x})",
+            expected.len()
+        );
+        return true;
+    };
+
+    let patch = &expected[first..=last_diff];
+    let patch_hex: String = patch.iter().map(|b| format!("{b:02x}")).collect();
+    println!(
+        "To fix: hextool -f {} -w {patch_hex} -o 0x{:x}",
+        path.display(),
+        offset + first as u64
+    );
+    false
+}
+
+fn run_unframe(path: &PathBuf, offset: u64, opts: &ReadOpts) {
+    let mut file = std::fs::File::open(path).unwrap_or_else(|e| {
+        die(&format!("failed to open file '{:?}': {e}", path));
+    });
+
+    let len = file
+        .metadata()
+        .map(|m| m.len())
+        .unwrap_or_else(|e| die(&format!("failed to stat file '{:?}': {e}", path)));
+
+    if offset.saturating_add(4) > len {
+        die("invalid offset (no room for a 4-byte frame length)");
+    }
+
+    file.seek(SeekFrom::Start(offset))
+        .unwrap_or_else(|e| die(&format!("failed to seek: {e}")));
+    let mut len_buf = [0u8; 4];
+    file.read_exact(&mut len_buf)
+        .unwrap_or_else(|e| die(&format!("failed to read frame length: {e}")));
+    let frame_len = u32::from_be_bytes(len_buf) as u64;
+
+    let available = len - offset - 4;
+    if frame_len > available {
+        die(&format!(
+            "frame length {frame_len} exceeds remaining file size ({available} bytes)"
+        ));
+    }
+
+    println!("Frame length: {frame_len} bytes");
+    run_read(path, offset + 4, Some(frame_len), opts, None);
+}
+
+fn run_write(path: &PathBuf, offset: u64, bytes: &[u8]) {
     let mut file = OpenOptions::new()
         .create(true)
         .read(true)
@@ -254,13 +1402,336 @@ fn run_write(path: &PathBuf, offset: u64, hex: &str) {
 
     file.seek(SeekFrom::Start(offset))
         .unwrap_or_else(|e| die(&format!("failed to seek: {e}")));
-    file.write_all(&bytes)
+    file.write_all(bytes)
         .unwrap_or_else(|e| die(&format!("failed to write: {e}")));
     file.flush()
         .unwrap_or_else(|e| die(&format!("failed to flush: {e}")));
 
     println!("Writing {} bytes at offset 0x{:08x}", bytes.len(), offset);
-    println!("Hex: {}", bytes_to_spaced_hex(&bytes));
-    println!("ASCII: {}", bytes_to_ascii(&bytes));
+    println!("Hex: {}", bytes_to_spaced_hex(bytes));
+    println!("ASCII: {}", bytes_to_ascii(bytes));
     println!("Successfully written");
 }
+
+const MOVE_CHUNK: usize = 8192;
+
+fn run_move(path: &PathBuf, mv: MoveSpec, dry_run: bool) {
+    let len = file_len_or_zero(path);
+    let src_end = mv
+        .src
+        .checked_add(mv.len)
+        .unwrap_or_else(|| die("invalid move (overflow)"));
+    if src_end > len {
+        die(&format!(
+            "source range 0x{:x}..0x{src_end:x} is past end of file ({len} bytes)",
+            mv.src
+        ));
+    }
+    let dst_end = mv
+        .dst
+        .checked_add(mv.len)
+        .unwrap_or_else(|| die("invalid move (overflow)"));
+    let new_len = len.max(dst_end);
+    let overlap = mv.src < dst_end && mv.dst < src_end && mv.src != mv.dst;
+
+    if !dry_run {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .unwrap_or_else(|e| die(&format!("failed to open file '{:?}': {e}", path)));
+        move_range(&mut file, len, mv, MOVE_CHUNK)
+            .unwrap_or_else(|e| die(&format!("move failed: {e}")));
+        file.flush()
+            .unwrap_or_else(|e| die(&format!("failed to flush: {e}")));
+    }
+
+    let prefix = if dry_run {
+        "[dry-run] would move"
+    } else {
+        "Moved"
+    };
+    println!(
+        "{prefix} {} bytes from 0x{:08x} to 0x{:08x}{}",
+        mv.len,
+        mv.src,
+        mv.dst,
+        if overlap { " (overlapping)" } else { "" }
+    );
+    if mv.dst > len {
+        println!("Gap of {} bytes filled with 0x20", mv.dst - len);
+    }
+    if new_len != len {
+        println!("File size: {len} -> {new_len} bytes");
+    }
+}
+
+// Plage par défaut: de --offset à la fin du fichier, tronquée à la fin du fichier
+fn run_swap_nibbles(path: &PathBuf, offset: u64, size: Option<u64>, dry_run: bool) {
+    let len = file_len_or_zero(path);
+    if offset > len {
+        die("invalid offset (past end of file)");
+    }
+    let count = size.unwrap_or(len - offset).min(len - offset);
+
+    if !dry_run {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .unwrap_or_else(|e| die(&format!("failed to open file '{:?}': {e}", path)));
+        swap_nibbles_range(&mut file, offset, count, MOVE_CHUNK)
+            .unwrap_or_else(|e| die(&format!("swap failed: {e}")));
+        file.flush()
+            .unwrap_or_else(|e| die(&format!("failed to flush: {e}")));
+    }
+
+    let prefix = if dry_run {
+        "[dry-run] would swap nibbles of"
+    } else {
+        "Swapped nibbles of"
+    };
+    println!("{prefix} {count} bytes at 0x{offset:08x}");
+}
+
+// Lecture, transformation et réécriture par blocs: appliquée deux fois, rend l'original
+fn swap_nibbles_range<F: Read + Write + Seek>(
+    file: &mut F,
+    offset: u64,
+    count: u64,
+    chunk: usize,
+) -> std::io::Result<()> {
+    let mut buf = vec![0u8; chunk];
+    let mut done = 0u64;
+    while done < count {
+        let n = (count - done).min(chunk as u64);
+        let part = &mut buf[..n as usize];
+        file.seek(SeekFrom::Start(offset + done))?;
+        file.read_exact(part)?;
+        for b in part.iter_mut() {
+            *b = b.rotate_left(4);
+        }
+        file.seek(SeekFrom::Start(offset + done))?;
+        file.write_all(part)?;
+        done += n;
+    }
+    Ok(())
+}
+
+// Comme memmove: copie par blocs, à rebours si la destination est après la source,
+// pour ne jamais écraser des octets pas encore lus
+fn move_range<F: Read + Write + Seek>(
+    file: &mut F,
+    file_len: u64,
+    mv: MoveSpec,
+    chunk: usize,
+) -> std::io::Result<()> {
+    if mv.dst > file_len {
+        file.seek(SeekFrom::End(0))?;
+        let filler = vec![0x20u8; chunk];
+        let mut gap = mv.dst - file_len;
+        while gap > 0 {
+            let n = (gap as usize).min(chunk);
+            file.write_all(&filler[..n])?;
+            gap -= n as u64;
+        }
+    }
+
+    let backward = mv.dst > mv.src;
+    let mut buf = vec![0u8; chunk];
+    let mut done = 0u64;
+    while done < mv.len {
+        let n = (mv.len - done).min(chunk as u64);
+        let rel = if backward { mv.len - done - n } else { done };
+        let part = &mut buf[..n as usize];
+        file.seek(SeekFrom::Start(mv.src + rel))?;
+        file.read_exact(part)?;
+        file.seek(SeekFrom::Start(mv.dst + rel))?;
+        file.write_all(part)?;
+        done += n;
+    }
+    Ok(())
+}
+
+fn dry_run_write(offset: u64, bytes: &[u8], len: u64) -> u64 {
+    let gap = offset.saturating_sub(len);
+    let end = offset
+        .checked_add(bytes.len() as u64)
+        .unwrap_or_else(|| die("offset + length overflows"));
+    let new_len = len.max(end);
+
+    println!(
+        "[dry-run] would write {} bytes at 0x{:08x}",
+        bytes.len(),
+        offset
+    );
+    if gap > 0 {
+        println!("[dry-run] would fill {gap} bytes of gap with 0x20");
+    }
+    println!("[dry-run] file size: {len} -> {new_len} bytes");
+    println!("Hex: {}", bytes_to_spaced_hex(bytes));
+    println!("ASCII: {}", bytes_to_ascii(bytes));
+    new_len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn moved(data: &[u8], src: u64, dst: u64, len: u64) -> Vec<u8> {
+        let mut file = Cursor::new(data.to_vec());
+        let mv = MoveSpec { src, dst, len };
+        // Petits blocs pour que le chevauchement traverse plusieurs copies
+        move_range(&mut file, data.len() as u64, mv, 3).unwrap();
+        file.into_inner()
+    }
+
+    #[test]
+    fn move_handles_overlap_both_ways() {
+        let data = b"0123456789";
+        assert_eq!(moved(data, 0, 2, 7), b"0101234569");
+        assert_eq!(moved(data, 2, 0, 7), b"2345678789");
+        assert_eq!(moved(data, 1, 6, 3), b"0123451239");
+        assert_eq!(moved(data, 4, 4, 5), data);
+    }
+
+    // Accepte `room` octets puis refuse tout
+    struct Full {
+        got: Vec<u8>,
+        room: usize,
+    }
+
+    impl Write for Full {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.got.len() + buf.len() > self.room {
+                return Err(std::io::Error::other("disk full"));
+            }
+            self.got.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn tee_counts_bytes_and_stops_on_write_error() {
+        let mut tee = Tee::new(Vec::new());
+        tee.write(b"0123456789abcdef");
+        tee.write(b"xyz");
+        assert_eq!(tee.sink.as_deref(), Some(&b"0123456789abcdefxyz"[..]));
+        assert_eq!(tee.close().unwrap(), 19);
+
+        let mut tee = Tee::new(Full {
+            got: Vec::new(),
+            room: 20,
+        });
+        tee.write(&[1; 16]);
+        tee.write(&[2; 16]);
+        tee.write(&[3; 2]);
+        assert!(tee.sink.is_none());
+        assert_eq!(tee.written, 16);
+        assert!(tee.close().is_err());
+    }
+
+    #[test]
+    fn swap_nibbles_round_trips() {
+        let data = b"\xab\x12\x00\xff\x9c\x34".to_vec();
+        let mut file = Cursor::new(data.clone());
+        swap_nibbles_range(&mut file, 1, 4, 3).unwrap();
+        assert_eq!(file.get_ref(), b"\xab\x21\x00\xff\xc9\x34");
+        swap_nibbles_range(&mut file, 1, 4, 3).unwrap();
+        assert_eq!(file.into_inner(), data);
+    }
+
+    #[test]
+    fn follow_sees_appends_and_truncation() {
+        let mut file = Cursor::new(b"abcdef".to_vec());
+        let mut pos = 2;
+        assert_eq!(
+            poll_appended(&mut file, &mut pos, 6).unwrap(),
+            Polled::Appended(b"cdef".to_vec())
+        );
+        assert_eq!(pos, 6);
+        assert_eq!(poll_appended(&mut file, &mut pos, 6).unwrap(), Polled::Idle);
+
+        file.get_mut().extend_from_slice(b"gh");
+        assert_eq!(
+            poll_appended(&mut file, &mut pos, 8).unwrap(),
+            Polled::Appended(b"gh".to_vec())
+        );
+
+        // Tronqué sous la position: on repart de la nouvelle fin
+        file.get_mut().truncate(3);
+        assert_eq!(
+            poll_appended(&mut file, &mut pos, 3).unwrap(),
+            Polled::Truncated
+        );
+        assert_eq!(pos, 3);
+    }
+
+    #[test]
+    fn offset_digits_grow_past_4_gib() {
+        assert_eq!(offset_digits_for(0), 8);
+        assert_eq!(offset_digits_for(0xFFFF_FFFF), 8);
+        assert_eq!(offset_digits_for(0x1_0000_0000), 9);
+        assert_eq!(offset_digits_for(u64::MAX), 16);
+    }
+
+    #[test]
+    fn crc32_and_sidecar_parsing() {
+        assert_eq!(!crc32_update(!0, b"123456789"), 0xCBF4_3926);
+        // Par morceaux: même résultat qu'en une fois
+        assert_eq!(
+            !crc32_update(crc32_update(!0, b"1234"), b"56789"),
+            0xCBF4_3926
+        );
+        assert_eq!(!crc32_update(!0, b""), 0);
+
+        assert_eq!(parse_sidecar_crc("cbf43926\n"), Ok(0xCBF4_3926));
+        assert_eq!(
+            parse_sidecar_crc("  0xCBF43926  file.bin\n"),
+            Ok(0xCBF4_3926)
+        );
+        assert!(parse_sidecar_crc("").is_err());
+        assert!(parse_sidecar_crc("123456789").is_err());
+        assert!(parse_sidecar_crc("xyz").is_err());
+    }
+
+    #[test]
+    fn ranges_file_parsing() {
+        let ranges = parse_ranges("0 16\n# commentaire\n\n0x20 0x8  # en-tête\n").unwrap();
+        assert_eq!(ranges, vec![(1, 0, 16), (4, 0x20, 8)]);
+        assert_eq!(
+            parse_ranges("0 4\n10\n").unwrap_err(),
+            "line 2: expected 'OFFSET SIZE', got '10'"
+        );
+        assert!(
+            parse_ranges("0 4\n0xZZ 4\n")
+                .unwrap_err()
+                .starts_with("line 2:")
+        );
+    }
+
+    #[test]
+    fn detect_matches_magic_bytes() {
+        assert_eq!(detect(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some("PNG"));
+        assert_eq!(detect(b"\x7fELF\x02\x01\x01"), Some("ELF"));
+        assert_eq!(detect(b"RIFF\x24\0\0\0WAVEfmt "), Some("WAV"));
+        let mut tar = vec![0u8; 300];
+        tar[257..262].copy_from_slice(b"ustar");
+        assert_eq!(detect(&tar), Some("TAR"));
+        // Trop court pour la signature: pas de faux positif ni de panique
+        assert_eq!(detect(b"\x89PN"), None);
+        assert_eq!(detect(b""), None);
+        assert_eq!(detect(b"hello world"), None);
+    }
+
+    #[test]
+    fn move_past_eof_fills_the_gap() {
+        assert_eq!(moved(b"abcd", 0, 6, 3), b"abcd  abc");
+        assert_eq!(moved(b"abcd", 1, 3, 3), b"abcbcd");
+    }
+}
@@ -1,14 +1,21 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use rand::Rng;
 use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream, ToSocketAddrs};
-use std::time::Duration;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 const P: u64 = 0xD87FA3E29184CF73;
 const G: u64 = 2;
 
 const IO_TIMEOUT: Duration = Duration::from_secs(10);
 const MAX_MSG_LEN: u32 = 1_048_576; // 1 MiB
+// Au-delà, une trame trop grande n'est pas vidée: la connexion est abandonnée
+const MAX_DISCARD: u32 = 4 * MAX_MSG_LEN;
+
+// Annonce envoyée par chaque pair avant l'échange DH: "SC", version, chiffrement
+const HELLO_MAGIC: [u8; 2] = *b"SC";
+const PROTO_VERSION: u8 = 1;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -20,61 +27,296 @@ const MAX_MSG_LEN: u32 = 1_048_576; // 1 MiB
 struct Cli {
     #[command(subcommand)]
     cmd: Command,
+
+    /// Pad each message up to a multiple of N bytes (both peers must use the same setting)
+    #[arg(
+        long = "pad-block",
+        value_name = "N",
+        global = true,
+        value_parser = clap::value_parser!(u32).range(1..=MAX_MSG_LEN as i64)
+    )]
+    pad_block: Option<u32>,
+
+    /// Hexdump every raw byte sent and received to stderr
+    #[arg(long = "trace", global = true)]
+    trace: bool,
+
+    /// Derive a fresh keystream every K messages in each direction (both peers must agree)
+    #[arg(
+        long = "rekey",
+        value_name = "K",
+        global = true,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    rekey: Option<u32>,
+
+    /// Pre-shared key mixed into the DH secret (both peers must use the same value)
+    #[arg(long = "psk", value_name = "STR", global = true)]
+    psk: Option<String>,
+
+    /// Skip the hello and key-confirmation exchanges, for peers doing plain DH (both peers must
+    /// agree). The cipher is then not negotiated, and a key or cipher mismatch only surfaces as
+    /// undecryptable messages
+    #[arg(long = "no-proof", global = true)]
+    no_proof: bool,
+
+    /// Keystream cipher for the session (both peers must agree; a mismatch aborts the handshake).
+    /// Sessions used xor-lcg before this option existed: pass it to talk to such peers
+    #[arg(
+        long = "cipher",
+        value_name = "NAME",
+        global = true,
+        value_enum,
+        default_value_t = Cipher::ChaCha20
+    )]
+    cipher: Cipher,
+
+    /// Also print per-message byte counts and timings
+    #[arg(short = 'v', long = "verbose", global = true, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Print only errors and the secure-channel confirmation
+    #[arg(long = "quiet", global = true)]
+    quiet: bool,
+}
+
+// Niveau des messages d'état; les erreurs (stderr) sont toujours affichées
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+enum Verbosity {
+    Quiet,
+    #[default]
+    Normal,
+    Verbose,
+}
+
+/// Keystream generators selectable with `--cipher`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+enum Cipher {
+    /// 32-bit LCG: the original toy, trivially predictable (for demos only)
+    XorLcg,
+    /// ChaCha20 (RFC 8439) keyed from the 64-bit session secret
+    #[default]
+    #[value(name = "chacha20")]
+    ChaCha20,
+}
+
+impl Cipher {
+    // Identifiant sur le fil: ne jamais renuméroter, seulement ajouter
+    fn id(self) -> u8 {
+        match self {
+            Cipher::XorLcg => 1,
+            Cipher::ChaCha20 => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        [Cipher::XorLcg, Cipher::ChaCha20]
+            .into_iter()
+            .find(|c| c.id() == id)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Cipher::XorLcg => "xor-lcg",
+            Cipher::ChaCha20 => "chacha20",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct ChatOpts {
+    pad_block: Option<u32>,
+    trace: bool,
+    rekey: Option<u32>,
+    psk: Option<String>,
+    no_proof: bool,
+    cipher: Cipher,
+    verbosity: Verbosity,
+}
+
+impl ChatOpts {
+    /// Regular status lines (hidden by --quiet).
+    fn status(&self) -> bool {
+        self.verbosity >= Verbosity::Normal
+    }
+
+    /// Per-message details (--verbose only).
+    fn details(&self) -> bool {
+        self.verbosity == Verbosity::Verbose
+    }
 }
 
 #[derive(Subcommand, Debug)]
 enum Command {
     /// Start server
     Server {
-        /// Port to listen on (1-65535)
+        /// Port to listen on (1-65535, or 0 to let the OS pick a free one)
         port: u16,
+
+        /// Message sent to each client after the handshake
+        #[arg(
+            long = "server-msg",
+            value_name = "STR",
+            default_value = "Hello",
+            value_parser = parse_demo_msg
+        )]
+        server_msg: String,
+
+        /// Receive and discard messages until the client disconnects (for `bench`)
+        #[arg(long = "drain", conflicts_with = "server_msg")]
+        drain: bool,
+
+        /// Wait MS milliseconds after the handshake before sending the greeting
+        #[arg(
+            long = "delay",
+            value_name = "MS",
+            default_value_t = 0,
+            conflicts_with = "drain"
+        )]
+        delay: u64,
     },
     /// Connect to server
     Client {
         /// Address in the form host:port (e.g. localhost:8080)
         addr: String,
+
+        /// Send the contents of this file as the reply instead of "Hi!"
+        #[arg(long = "message-file", value_name = "PATH")]
+        message_file: Option<PathBuf>,
+
+        /// Reply sent back to the server (instead of "Hi!")
+        #[arg(
+            long = "client-msg",
+            value_name = "STR",
+            conflicts_with = "message_file",
+            value_parser = parse_demo_msg
+        )]
+        client_msg: Option<String>,
+
+        /// Only perform the handshake, print the fingerprint and timing, then disconnect
+        #[arg(long = "probe", conflicts_with_all = ["message_file", "client_msg"])]
+        probe: bool,
+    },
+    /// Measure raw throughput against a server started with --drain
+    Bench {
+        /// Address in the form host:port (e.g. localhost:8080)
+        addr: String,
+
+        /// Number of messages to send
+        #[arg(long = "count", value_name = "N", default_value_t = 1000)]
+        count: u32,
+
+        /// Size of each message in bytes
+        #[arg(
+            long = "size",
+            value_name = "BYTES",
+            default_value_t = 1024,
+            value_parser = clap::value_parser!(u32).range(0..=MAX_MSG_LEN as i64)
+        )]
+        size: u32,
     },
 }
 
 fn main() {
     let cli = Cli::parse();
+    let opts = ChatOpts {
+        pad_block: cli.pad_block,
+        trace: cli.trace,
+        rekey: cli.rekey,
+        psk: cli.psk,
+        no_proof: cli.no_proof,
+        cipher: cli.cipher,
+        verbosity: if cli.quiet {
+            Verbosity::Quiet
+        } else if cli.verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        },
+    };
 
     let code = match cli.cmd {
-        Command::Server { port } => match run_server(port) {
+        Command::Server {
+            port,
+            server_msg,
+            drain,
+            delay,
+        } => match run_server(
+            port,
+            &server_msg,
+            drain,
+            Duration::from_millis(delay),
+            &opts,
+        ) {
             Ok(()) => 0,
             Err(e) => {
                 eprintln!("error: {e}");
                 1
             }
         },
-        Command::Client { addr } => match run_client(&addr) {
-            Ok(()) => 0,
-            Err(AppError::Cli(msg)) => {
-                eprintln!("error: {msg}");
-                2
-            }
-            Err(AppError::Runtime(msg)) => {
-                eprintln!("error: {msg}");
-                1
-            }
-        },
+        Command::Client {
+            addr,
+            message_file,
+            client_msg,
+            probe,
+        } => exit_code(run_client(&addr, message_file, client_msg, probe, &opts)),
+        Command::Bench { addr, count, size } => exit_code(run_bench(&addr, count, size, &opts)),
     };
 
     std::process::exit(code);
 }
 
-fn run_server(port: u16) -> Result<(), String> {
+fn exit_code(res: Result<(), AppError>) -> i32 {
+    match res {
+        Ok(()) => 0,
+        Err(AppError::Cli(msg)) => {
+            eprintln!("error: {msg}");
+            2
+        }
+        Err(AppError::Runtime(msg)) => {
+            eprintln!("error: {msg}");
+            1
+        }
+    }
+}
+
+fn run_server(
+    port: u16,
+    greeting: &str,
+    drain: bool,
+    delay: Duration,
+    opts: &ChatOpts,
+) -> Result<(), String> {
+    // read_full retente MAX_STALLS fois: le client patiente (MAX_STALLS + 1) x IO_TIMEOUT
+    let budget = IO_TIMEOUT * (MAX_STALLS + 1);
+    if delay >= budget {
+        eprintln!(
+            "warning: --delay {} ms is not below the {} s clients wait for data; they will time out before the greeting",
+            delay.as_millis(),
+            budget.as_secs()
+        );
+    }
+
     // Runner expectation: server prints a line containing "p =" and stays alive.
-    println!("[DH] Using hardcoded DH parameters:");
-    println!("p = {P:016X}");
-    println!("g = {G}");
-    println!();
+    if opts.status() {
+        println!("[DH] Using hardcoded DH parameters:");
+        println!("p = {P:016X}");
+        println!("g = {G}");
+        println!("cipher = {}", opts.cipher.name());
+        println!();
+    }
 
     let addr = format!("0.0.0.0:{port}");
     let listener = TcpListener::bind(&addr).map_err(|e| format!("bind({addr}) failed: {e}"))?;
+    // Avec le port 0, l'OS choisit: on affiche le port réellement attribué
+    let addr = listener
+        .local_addr()
+        .map_err(|e| format!("local_addr failed: {e}"))?;
 
-    println!("[SERVER] Listening on {addr}");
-    println!("[SERVER] Waiting for client...");
+    if opts.status() {
+        println!("[SERVER] Listening on {addr}");
+        println!("[SERVER] Waiting for client...");
+    }
 
     loop {
         let (mut stream, peer) = match listener.accept() {
@@ -85,43 +327,239 @@ fn run_server(port: u16) -> Result<(), String> {
             }
         };
 
-        println!("[CLIENT] Connected from {peer}");
+        if opts.status() {
+            println!("[CLIENT] Connected from {peer}");
+        }
 
         if let Err(e) = configure_stream(&mut stream) {
             eprintln!("error: stream config failed: {e}");
             continue;
         }
 
-        if let Err(e) = handle_server_session(&mut stream) {
+        let res = if drain {
+            drain_session(&mut stream, opts)
+        } else {
+            handle_server_session(&mut stream, greeting.as_bytes(), delay, opts)
+        };
+        if let Err(e) = res {
             eprintln!("error: session failed: {e}");
         }
 
-        println!("[SERVER] Waiting for client...");
+        if opts.status() {
+            println!("[SERVER] Waiting for client...");
+        }
     }
 }
 
-fn run_client(addr: &str) -> Result<(), AppError> {
+fn run_client(
+    addr: &str,
+    message_file: Option<PathBuf>,
+    client_msg: Option<String>,
+    probe: bool,
+    opts: &ChatOpts,
+) -> Result<(), AppError> {
     let endpoint = parse_endpoint(addr).map_err(AppError::Cli)?;
 
+    // Lu avant la connexion pour échouer tôt si le fichier est absent ou trop gros
+    let reply = match (&message_file, client_msg) {
+        (Some(path), _) => read_message_file(path).map_err(AppError::Runtime)?,
+        (None, Some(msg)) => msg.into_bytes(),
+        (None, None) => b"Hi!".to_vec(),
+    };
+
+    let sockaddr = resolve(addr, &endpoint)?;
+
+    if opts.status() {
+        println!("[CLIENT] Connecting to {addr}...");
+    }
+    let started = Instant::now();
+    let mut stream = TcpStream::connect(sockaddr)
+        .map_err(|e| AppError::Runtime(format!("connect({addr}) failed: {e}")))?;
+    if opts.status() {
+        println!("[CLIENT] Connected!");
+    }
+
+    configure_stream(&mut stream)
+        .map_err(|e| AppError::Runtime(format!("stream config failed: {e}")))?;
+
+    if probe {
+        return Ok(probe_session(&mut stream, started, opts)?);
+    }
+
+    handle_client_session(&mut stream, &reply, opts)?;
+
+    if let Some(path) = &message_file
+        && opts.status()
+    {
+        println!(
+            "[CLIENT] Sent {} bytes from {}",
+            reply.len(),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+fn resolve(addr: &str, endpoint: &str) -> Result<SocketAddr, AppError> {
     let mut resolved = endpoint
         .to_socket_addrs()
         .map_err(|e| AppError::Cli(format!("invalid address '{addr}': {e}")))?;
 
-    let Some(sockaddr) = resolved.next() else {
-        return Err(AppError::Cli(format!(
-            "invalid address '{addr}': could not resolve"
-        )));
-    };
+    resolved
+        .next()
+        .ok_or_else(|| AppError::Cli(format!("invalid address '{addr}': could not resolve")))
+}
+
+// Débit brut: N messages de taille fixe, puis attente de la fermeture côté serveur (--drain)
+fn run_bench(addr: &str, count: u32, size: u32, opts: &ChatOpts) -> Result<(), AppError> {
+    let endpoint = parse_endpoint(addr).map_err(AppError::Cli)?;
+    let sockaddr = resolve(addr, &endpoint)?;
 
-    println!("[CLIENT] Connecting to {addr}...");
+    if opts.status() {
+        println!("[BENCH] Connecting to {addr}...");
+    }
     let mut stream = TcpStream::connect(sockaddr)
         .map_err(|e| AppError::Runtime(format!("connect({addr}) failed: {e}")))?;
-    println!("[CLIENT] Connected!");
-
     configure_stream(&mut stream)
         .map_err(|e| AppError::Runtime(format!("stream config failed: {e}")))?;
 
-    handle_client_session(&mut stream).map_err(AppError::Runtime)
+    let wire = &mut Wire::new(&mut stream, opts.trace);
+    let mut keys = dh_handshake(
+        wire,
+        Role::Client,
+        opts.psk.as_deref(),
+        !opts.no_proof,
+        opts.cipher,
+    )?;
+    wire.set_phase("message");
+
+    let payload = vec![0x42u8; size as usize];
+    let started = Instant::now();
+    for i in 0..count {
+        send_msg(wire, &keys.send, keys.sent, &payload, opts.pad_block)
+            .map_err(|e| AppError::Runtime(format!("message {i}: {e}")))?;
+        keys.after_send(opts.rekey);
+    }
+
+    // Le serveur ferme après avoir tout lu: la mesure couvre aussi le déchiffrement
+    stream
+        .shutdown(Shutdown::Write)
+        .map_err(|e| AppError::Runtime(format!("shutdown failed: {e}")))?;
+    let mut rest = Vec::new();
+    stream
+        .read_to_end(&mut rest)
+        .map_err(|e| AppError::Runtime(format!("waiting for server failed: {e}")))?;
+    let secs = started.elapsed().as_secs_f64().max(f64::EPSILON);
+
+    let total = count as u64 * size as u64;
+    println!(
+        "[BENCH] Sent {count} messages x {size} bytes ({:.2} MB) in {secs:.3} s",
+        total as f64 / 1e6
+    );
+    println!(
+        "[BENCH] {:.1} msg/s, {:.2} MB/s",
+        count as f64 / secs,
+        total as f64 / 1e6 / secs
+    );
+    Ok(())
+}
+
+// Côté serveur du bench: lit et jette les messages jusqu'à la fermeture du client
+fn drain_session(stream: &mut TcpStream, opts: &ChatOpts) -> Result<(), SessionError> {
+    let stream = &mut Wire::new(stream, opts.trace);
+    let mut keys = session_handshake(stream, Role::Server, opts)?;
+
+    let mut messages = 0u64;
+    let mut bytes = 0u64;
+    loop {
+        match recv_msg(stream, &keys.recv, keys.received, opts.pad_block) {
+            Ok(msg) => {
+                messages += 1;
+                bytes += msg.len() as u64;
+                keys.after_recv(opts.rekey);
+                if opts.details() {
+                    println!("[VERBOSE] recv #{messages}: {} bytes", msg.len());
+                }
+            }
+            Err(e) if e.io_kind() == Some(std::io::ErrorKind::UnexpectedEof) => break,
+            Err(e) => {
+                eprintln!("error: drained {messages} messages before the failure");
+                return Err(e);
+            }
+        }
+    }
+
+    if opts.status() {
+        println!("[SERVER] Drained {messages} messages ({bytes} bytes)");
+    }
+    Ok(())
+}
+
+// Handshake seul: vérifie que le serveur parle le protocole, puis ferme proprement
+fn probe_session(
+    stream: &mut TcpStream,
+    started: Instant,
+    opts: &ChatOpts,
+) -> Result<(), SessionError> {
+    let connected = started.elapsed();
+    let keys = {
+        let mut wire = Wire::new(&mut *stream, opts.trace);
+        dh_handshake(
+            &mut wire,
+            Role::Client,
+            opts.psk.as_deref(),
+            !opts.no_proof,
+            opts.cipher,
+        )?
+    };
+    let total = started.elapsed();
+
+    println!("[PROBE] Handshake OK");
+    println!(
+        "[PROBE] Fingerprint: {}",
+        format_fingerprint(keys.fingerprint)
+    );
+    println!(
+        "[PROBE] Connect: {:.1} ms, handshake: {:.1} ms",
+        connected.as_secs_f64() * 1000.0,
+        (total - connected).as_secs_f64() * 1000.0
+    );
+
+    stream
+        .shutdown(Shutdown::Both)
+        .map_err(|source| SessionError::Io {
+            context: "shutdown",
+            source,
+        })
+}
+
+fn format_fingerprint(fp: u64) -> String {
+    let hex = format!("{fp:016X}");
+    let groups: Vec<&str> = (0..4).map(|i| &hex[i * 4..i * 4 + 4]).collect();
+    groups.join("-")
+}
+
+fn read_message_file(path: &Path) -> Result<Vec<u8>, String> {
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("failed to read '{}': {e}", path.display()))?;
+    if bytes.len() > MAX_MSG_LEN as usize {
+        return Err(format!(
+            "'{}' is {} bytes, larger than the {MAX_MSG_LEN} byte message limit",
+            path.display(),
+            bytes.len()
+        ));
+    }
+    Ok(bytes)
+}
+
+fn parse_demo_msg(raw: &str) -> Result<String, String> {
+    if raw.len() > MAX_MSG_LEN as usize {
+        return Err(format!(
+            "message is {} bytes, larger than the {MAX_MSG_LEN} byte limit",
+            raw.len()
+        ));
+    }
+    Ok(raw.to_string())
 }
 
 fn configure_stream(stream: &mut TcpStream) -> std::io::Result<()> {
@@ -131,39 +569,107 @@ fn configure_stream(stream: &mut TcpStream) -> std::io::Result<()> {
     Ok(())
 }
 
-fn handle_server_session(stream: &mut TcpStream) -> Result<(), String> {
-    println!("[DH] Starting key exchange...");
-
-    let keys = dh_handshake(stream, Role::Server).map_err(|e| format!("handshake failed: {e}"))?;
-
-    println!("Secure channel established.");
-
-    // Démo déterministe: envoi "Hello", réception d'une réponse.
-    let msg = b"Hello";
-    send_msg(stream, &keys.send, msg).map_err(|e| format!("send failed: {e}"))?;
+fn handle_server_session(
+    stream: &mut TcpStream,
+    greeting: &[u8],
+    delay: Duration,
+    opts: &ChatOpts,
+) -> Result<(), SessionError> {
+    let stream = &mut Wire::new(stream, opts.trace);
+    let mut keys = session_handshake(stream, Role::Server, opts)?;
+
+    if !delay.is_zero() {
+        if opts.details() {
+            println!(
+                "[VERBOSE] waiting {} ms before the greeting",
+                delay.as_millis()
+            );
+        }
+        std::thread::sleep(delay);
+    }
 
-    //lecture d'une réponse, sans faire échouer la session si le client ferme.
-    if let Ok(reply) = recv_msg(stream, &keys.recv) {
-        println!("[SERVER] {}", String::from_utf8_lossy(&reply));
+    // Démo déterministe: envoi du message d'accueil ("Hello" par défaut), réception d'une réponse.
+    let started = Instant::now();
+    send_msg(stream, &keys.send, keys.sent, greeting, opts.pad_block)?;
+    keys.after_send(opts.rekey);
+    log_transfer(opts, "sent", greeting.len(), started);
+
+    //lecture d'une réponse: une erreur est signalée sans faire échouer la session.
+    let started = Instant::now();
+    match recv_msg(stream, &keys.recv, keys.received, opts.pad_block) {
+        Ok(reply) => {
+            keys.after_recv(opts.rekey);
+            log_transfer(opts, "received", reply.len(), started);
+            if opts.status() {
+                println!("[SERVER] {}", String::from_utf8_lossy(&reply));
+            }
+        }
+        Err(e) => eprintln!("error: {e}"),
     }
 
     Ok(())
 }
 
-fn handle_client_session(stream: &mut TcpStream) -> Result<(), String> {
-    println!("[DH] Starting key exchange...");
+fn handle_client_session(
+    stream: &mut TcpStream,
+    reply: &[u8],
+    opts: &ChatOpts,
+) -> Result<(), SessionError> {
+    let stream = &mut Wire::new(stream, opts.trace);
+    let mut keys = session_handshake(stream, Role::Client, opts)?;
+
+    let started = Instant::now();
+    let incoming = recv_msg(stream, &keys.recv, keys.received, opts.pad_block)?;
+    keys.after_recv(opts.rekey);
+    log_transfer(opts, "received", incoming.len(), started);
+    if opts.status() {
+        println!("[SERVER] {}", String::from_utf8_lossy(&incoming));
+    }
 
-    let keys = dh_handshake(stream, Role::Client).map_err(|e| format!("handshake failed: {e}"))?;
+    let started = Instant::now();
+    send_msg(stream, &keys.send, keys.sent, reply, opts.pad_block)?;
+    keys.after_send(opts.rekey);
+    log_transfer(opts, "sent", reply.len(), started);
 
-    println!("Secure channel established.");
+    Ok(())
+}
 
-    let incoming = recv_msg(stream, &keys.recv).map_err(|e| format!("recv failed: {e}"))?;
-    println!("[SERVER] {}", String::from_utf8_lossy(&incoming));
+// Échange DH commun aux sessions; la confirmation reste affichée même avec --quiet
+fn session_handshake<S: Read + Write>(
+    stream: &mut Wire<S>,
+    role: Role,
+    opts: &ChatOpts,
+) -> Result<Keys, SessionError> {
+    if opts.status() {
+        println!("[DH] Starting key exchange...");
+    }
+    let started = Instant::now();
+    let keys = dh_handshake(
+        stream,
+        role,
+        opts.psk.as_deref(),
+        !opts.no_proof,
+        opts.cipher,
+    )?;
+    if opts.details() {
+        println!(
+            "[VERBOSE] handshake: {:.2} ms",
+            started.elapsed().as_secs_f64() * 1000.0
+        );
+    }
 
-    let reply = b"Hi!";
-    send_msg(stream, &keys.send, reply).map_err(|e| format!("send failed: {e}"))?;
+    println!("Secure channel established.");
+    stream.set_phase("message");
+    Ok(keys)
+}
 
-    Ok(())
+fn log_transfer(opts: &ChatOpts, dir: &str, len: usize, started: Instant) {
+    if opts.details() {
+        println!(
+            "[VERBOSE] {dir} {len} bytes in {:.2} ms",
+            started.elapsed().as_secs_f64() * 1000.0
+        );
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -172,136 +678,601 @@ enum Role {
     Client,
 }
 
+// Graines directionnelles
+const SEED_S2C: u64 = 0x5352_563E_0000_0001; // "SRV>"
+const SEED_C2S: u64 = 0x434C_493E_0000_0002; // "CLI>"
+
 struct Keys {
     send: Keystream,
     recv: Keystream,
+    fingerprint: u64,
+    secret: u64,
+    role: Role,
+    cipher: Cipher,
+    sent: u64,
+    received: u64,
+}
+
+impl Keys {
+    fn new(secret: u64, role: Role, cipher: Cipher) -> Self {
+        let (send_dir, recv_dir) = role.directions();
+        Self {
+            send: Keystream::with_cipher(cipher, direction_seed(secret, send_dir, 0)),
+            recv: Keystream::with_cipher(cipher, direction_seed(secret, recv_dir, 0)),
+            fingerprint: mix64(secret ^ 0x4650_5249_4E54_0003), // "FPRINT"
+            secret,
+            role,
+            cipher,
+            sent: 0,
+            received: 0,
+        }
+    }
+
+    // Chaque sens compte ses messages: l'émetteur et le récepteur changent de clé au même message
+    fn after_send(&mut self, rekey: Option<u32>) {
+        self.sent += 1;
+        if rekey.is_some_and(|k| self.sent.is_multiple_of(k as u64)) {
+            let (send_dir, _) = self.role.directions();
+            let seed = direction_seed(self.secret, send_dir, self.sent);
+            self.send = Keystream::with_cipher(self.cipher, seed);
+        }
+    }
+
+    fn after_recv(&mut self, rekey: Option<u32>) {
+        self.received += 1;
+        if rekey.is_some_and(|k| self.received.is_multiple_of(k as u64)) {
+            let (_, recv_dir) = self.role.directions();
+            let seed = direction_seed(self.secret, recv_dir, self.received);
+            self.recv = Keystream::with_cipher(self.cipher, seed);
+        }
+    }
+}
+
+impl Role {
+    // (sens d'émission, sens de réception)
+    fn directions(self) -> (u64, u64) {
+        match self {
+            Role::Server => (SEED_S2C, SEED_C2S),
+            Role::Client => (SEED_C2S, SEED_S2C),
+        }
+    }
+}
+
+// Compteur 0 = clé initiale; ensuite le secret est remélangé avec le compteur de messages
+fn direction_seed(secret: u64, dir: u64, counter: u64) -> u64 {
+    let base = if counter == 0 {
+        secret
+    } else {
+        mix64(secret ^ counter)
+    };
+    mix64(base ^ dir)
+}
+
+// Flux enveloppé: avec --trace, chaque octet brut lu/écrit est dumpé sur stderr
+struct Wire<S> {
+    inner: S,
+    trace: bool,
+    phase: &'static str,
+}
+
+impl<S> Wire<S> {
+    fn new(inner: S, trace: bool) -> Self {
+        Self {
+            inner,
+            trace,
+            phase: "handshake",
+        }
+    }
+
+    fn set_phase(&mut self, phase: &'static str) {
+        self.phase = phase;
+    }
+}
+
+impl<S: Read> Read for Wire<S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if self.trace && n > 0 {
+            trace_dump("recv", self.phase, &buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+impl<S: Write> Write for Wire<S> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        if self.trace && n > 0 {
+            trace_dump("send", self.phase, &buf[..n]);
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn trace_dump(dir: &str, phase: &str, bytes: &[u8]) {
+    eprintln!("[TRACE] {dir} {phase} ({} bytes)", bytes.len());
+    for (i, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+        eprintln!("[TRACE]   {:04x}: {}", i * 16, hex.join(" "));
+    }
 }
 
-fn dh_handshake(stream: &mut TcpStream, role: Role) -> std::io::Result<Keys> {
+fn dh_handshake<S: Read + Write>(
+    stream: &mut Wire<S>,
+    role: Role,
+    psk: Option<&str>,
+    proof: bool,
+    cipher: Cipher,
+) -> Result<Keys, SessionError> {
+    let io = |source| SessionError::Io {
+        context: "handshake",
+        source,
+    };
+    // --no-proof vise les pairs DH « manuels »: ils n'envoient pas de hello non plus
+    if proof {
+        exchange_hello(stream, role, cipher)?;
+    }
+
     // Private in [2, P-2]
     let mut rng = rand::thread_rng();
     let private = rng.gen_range(2..(P - 1));
     let public = modexp(G, private, P);
 
     // Exchange public keys (8 bytes)
-    let peer_public = match role {
+    stream.set_phase("public-key");
+    let mut buf = [0u8; 8];
+    match role {
         Role::Server => {
-            stream.write_all(&public.to_be_bytes())?;
-            let mut buf = [0u8; 8];
-            stream.read_exact(&mut buf)?;
-            u64::from_be_bytes(buf)
+            stream.write_all(&public.to_be_bytes()).map_err(io)?;
+            stream.read_exact(&mut buf).map_err(io)?;
         }
         Role::Client => {
-            let mut buf = [0u8; 8];
-            stream.read_exact(&mut buf)?;
-            let peer = u64::from_be_bytes(buf);
-            stream.write_all(&public.to_be_bytes())?;
-            peer
+            stream.read_exact(&mut buf).map_err(io)?;
+            stream.write_all(&public.to_be_bytes()).map_err(io)?;
         }
-    };
+    }
+    let peer_public = u64::from_be_bytes(buf);
 
     // Basic validation of peer_public
     if peer_public <= 1 || peer_public >= (P - 1) {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "invalid peer public key",
+        return Err(SessionError::Handshake(
+            "invalid peer public key".to_string(),
         ));
     }
 
-    let secret = modexp(peer_public, private, P);
+    let secret = mix_psk(modexp(peer_public, private, P), psk);
+
+    // --no-proof: DH brut, un secret différent ne se verra qu'au déchiffrement des messages
+    if !proof {
+        return Ok(Keys::new(secret, role, cipher));
+    }
 
     // Proof exchange to detect mismatch
+    stream.set_phase("proof");
     let my_proof = mix64(secret ^ 0xA5A5_A5A5_A5A5_A5A5);
-    let peer_proof = match role {
+    match role {
         Role::Server => {
-            stream.write_all(&my_proof.to_be_bytes())?;
-            let mut buf = [0u8; 8];
-            stream.read_exact(&mut buf)?;
-            u64::from_be_bytes(buf)
+            stream.write_all(&my_proof.to_be_bytes()).map_err(io)?;
+            stream.read_exact(&mut buf).map_err(io)?;
         }
         Role::Client => {
-            let mut buf = [0u8; 8];
-            stream.read_exact(&mut buf)?;
-            let their = u64::from_be_bytes(buf);
-            stream.write_all(&my_proof.to_be_bytes())?;
-            their
+            stream.read_exact(&mut buf).map_err(io)?;
+            stream.write_all(&my_proof.to_be_bytes()).map_err(io)?;
         }
-    };
+    }
 
-    if peer_proof != my_proof {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "secret verification failed",
+    if u64::from_be_bytes(buf) != my_proof {
+        return Err(SessionError::Handshake(
+            "secret verification failed".to_string(),
         ));
     }
 
-    // Directional keystream seeds
-    let seed_s2c = mix64(secret ^ 0x5352_563E_0000_0001); // "SRV>"
-    let seed_c2s = mix64(secret ^ 0x434C_493E_0000_0002); // "CLI>"
+    Ok(Keys::new(secret, role, cipher))
+}
 
-    let (send_seed, recv_seed) = match role {
-        Role::Server => (seed_s2c, seed_c2s),
-        Role::Client => (seed_c2s, seed_s2c),
+// Chaque pair annonce version et chiffrement; le client écrit après avoir lu,
+// comme pour les clés publiques, et les deux côtés rejettent un désaccord
+fn exchange_hello<S: Read + Write>(
+    stream: &mut Wire<S>,
+    role: Role,
+    cipher: Cipher,
+) -> Result<(), SessionError> {
+    let io = |source| SessionError::Io {
+        context: "handshake",
+        source,
     };
+    stream.set_phase("hello");
+    let mine = [HELLO_MAGIC[0], HELLO_MAGIC[1], PROTO_VERSION, cipher.id()];
+    let mut peer = [0u8; 4];
+    match role {
+        Role::Server => {
+            stream.write_all(&mine).map_err(io)?;
+            stream.read_exact(&mut peer).map_err(io)?;
+        }
+        Role::Client => {
+            stream.read_exact(&mut peer).map_err(io)?;
+            stream.write_all(&mine).map_err(io)?;
+        }
+    }
 
-    Ok(Keys {
-        send: Keystream::new(send_seed),
-        recv: Keystream::new(recv_seed),
-    })
+    if peer[..2] != HELLO_MAGIC || peer[2] != PROTO_VERSION {
+        return Err(SessionError::Handshake(format!(
+            "peer is not a streamchat v{PROTO_VERSION} endpoint (hello {:02x?})",
+            peer
+        )));
+    }
+    match Cipher::from_id(peer[3]) {
+        Some(theirs) if theirs == cipher => Ok(()),
+        Some(theirs) => Err(SessionError::Handshake(format!(
+            "cipher mismatch: we use {}, peer uses {}",
+            cipher.name(),
+            theirs.name()
+        ))),
+        None => Err(SessionError::Handshake(format!(
+            "cipher mismatch: peer requested unknown cipher id {}",
+            peer[3]
+        ))),
+    }
 }
 
-fn send_msg(stream: &mut TcpStream, ks: &Keystream, plain: &[u8]) -> std::io::Result<()> {
-    let len_u32: u32 = plain
-        .len()
-        .try_into()
-        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "message too long"))?;
+// Numéro de séquence u64 BE en clair en tête de chaque trame: il sert de nonce au flux de la trame
+const SEQ_LEN: usize = 8;
 
-    if len_u32 > MAX_MSG_LEN {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidInput,
-            "message too large",
-        ));
+/// Frame received out of order: a gap (dropped frame) or a replay.
+#[derive(Debug)]
+struct SeqError {
+    expected: u64,
+    got: u64,
+}
+
+impl std::fmt::Display for SeqError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = if self.got < self.expected {
+            "replayed"
+        } else {
+            "dropped or reordered"
+        };
+        write!(
+            f,
+            "out-of-order frame: expected seq {}, got {} ({kind})",
+            self.expected, self.got
+        )
     }
+}
+
+impl std::error::Error for SeqError {}
+
+/// Why a session failed, so callers can match on the kind of failure.
+#[derive(Debug)]
+enum SessionError {
+    /// The peers could not agree on a key: bad hello, cipher or proof mismatch.
+    Handshake(String),
+    /// The transport failed (EOF, timeout, reset) during `context`.
+    Io {
+        context: &'static str,
+        source: std::io::Error,
+    },
+    /// The peer sent a malformed frame (bad length or padding).
+    Protocol(String),
+    /// A frame arrived out of sequence; kept apart so it can be logged and skipped.
+    OutOfOrder(SeqError),
+    /// A frame of `len` bytes is above `MAX_MSG_LEN`; `action` says what was done with it.
+    TooLarge { len: u64, action: &'static str },
+}
 
-    let mut local = ks.clone();
-    let mut cipher = vec![0u8; plain.len()];
-    for (i, &b) in plain.iter().enumerate() {
-        cipher[i] = b ^ local.next_byte();
+impl SessionError {
+    /// Kind of the underlying I/O error, for transport failures only.
+    fn io_kind(&self) -> Option<std::io::ErrorKind> {
+        match self {
+            SessionError::Io { source, .. } => Some(source.kind()),
+            _ => None,
+        }
     }
+}
 
-    stream.write_all(&len_u32.to_be_bytes())?;
-    stream.write_all(&cipher)?;
-    Ok(())
+impl std::fmt::Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionError::Handshake(msg) => write!(f, "handshake failed: {msg}"),
+            SessionError::Io { context, source } => write!(f, "{context} failed: {source}"),
+            SessionError::Protocol(msg) => write!(f, "protocol error: {msg}"),
+            SessionError::OutOfOrder(seq) => seq.fmt(f),
+            SessionError::TooLarge { len, action } => write!(
+                f,
+                "frame of {len} bytes exceeds the {MAX_MSG_LEN} byte limit ({action})"
+            ),
+        }
+    }
 }
 
-fn recv_msg(stream: &mut TcpStream, ks: &Keystream) -> std::io::Result<Vec<u8>> {
+impl std::error::Error for SessionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SessionError::Io { source, .. } => Some(source),
+            SessionError::OutOfOrder(seq) => Some(seq),
+            _ => None,
+        }
+    }
+}
+
+// Codes de sortie inchangés: toute erreur de session est une erreur d'exécution
+impl From<SessionError> for AppError {
+    fn from(e: SessionError) -> Self {
+        AppError::Runtime(e.to_string())
+    }
+}
+
+fn send_msg<W: Write, K: KeystreamCipher>(
+    stream: &mut W,
+    ks: &K,
+    seq: u64,
+    plain: &[u8],
+    pad_block: Option<u32>,
+) -> Result<(), SessionError> {
+    let padded;
+    let plain = match pad_block {
+        Some(block) => {
+            padded = pad_plaintext(plain, block)?;
+            padded.as_slice()
+        }
+        None => plain,
+    };
+
+    if plain.len() > MAX_MSG_LEN as usize {
+        return Err(SessionError::TooLarge {
+            len: plain.len() as u64,
+            action: "not sent",
+        });
+    }
+
+    let mut local = ks.for_frame(seq);
+    let mut cipher = Vec::with_capacity(SEQ_LEN + plain.len());
+    cipher.extend_from_slice(&seq.to_be_bytes());
+    cipher.extend(plain.iter().map(|b| b ^ local.next_byte()));
+
+    // Préfixe et corps dans un seul tampon: une reprise ne peut pas les désynchroniser
+    let len_u32 = cipher.len() as u32;
+    let mut frame = Vec::with_capacity(4 + cipher.len());
+    frame.extend_from_slice(&len_u32.to_be_bytes());
+    frame.extend_from_slice(&cipher);
+    write_full(stream, &frame).map_err(|source| SessionError::Io {
+        context: "send",
+        source,
+    })
+}
+
+/// Reads and decrypts one frame.
+///
+/// A length prefix above the message limit is a `TooLarge` error. Up to
+/// `MAX_DISCARD` bytes, the oversized body is read and dropped first so the
+/// stream stays aligned on the next frame; beyond that nothing more is read
+/// and the caller must close the connection. Either way the session ends.
+fn recv_msg<R: Read, K: KeystreamCipher>(
+    stream: &mut R,
+    ks: &K,
+    expected_seq: u64,
+    pad_block: Option<u32>,
+) -> Result<Vec<u8>, SessionError> {
+    let io = |source| SessionError::Io {
+        context: "recv",
+        source,
+    };
     let mut len_buf = [0u8; 4];
-    stream.read_exact(&mut len_buf)?;
+    read_full(stream, &mut len_buf, "length prefix").map_err(io)?;
     let len = u32::from_be_bytes(len_buf);
 
-    if len > MAX_MSG_LEN {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "incoming message too large",
+    if len > MAX_MSG_LEN + SEQ_LEN as u32 {
+        let action = if len <= MAX_DISCARD {
+            discard(stream, len as usize).map_err(io)?;
+            "discarded"
+        } else {
+            "too large to skip, closing"
+        };
+        return Err(SessionError::TooLarge {
+            len: len as u64,
+            action,
+        });
+    }
+    if (len as usize) < SEQ_LEN {
+        return Err(SessionError::Protocol(
+            "incoming frame too short for a sequence number".to_string(),
         ));
     }
 
     let mut cipher = vec![0u8; len as usize];
-    stream.read_exact(&mut cipher)?;
+    read_full(stream, &mut cipher, "frame body").map_err(io)?;
+
+    let mut seq_buf = [0u8; SEQ_LEN];
+    seq_buf.copy_from_slice(&cipher[..SEQ_LEN]);
+    let got = u64::from_be_bytes(seq_buf);
+    if got != expected_seq {
+        return Err(SessionError::OutOfOrder(SeqError {
+            expected: expected_seq,
+            got,
+        }));
+    }
+    cipher.drain(..SEQ_LEN);
 
-    let mut local = ks.clone();
+    let mut local = ks.for_frame(got);
     for b in &mut cipher {
         *b ^= local.next_byte();
     }
+
+    if pad_block.is_some() {
+        return unpad_plaintext(cipher);
+    }
     Ok(cipher)
 }
 
+// Délais d'attente consécutifs sans aucun octet transféré avant d'abandonner la trame
+const MAX_STALLS: u32 = 3;
+
+fn is_stall(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+    )
+}
+
+// Comme write_all, mais un timeout/WouldBlock reprend là où l'écriture s'était arrêtée
+fn write_full<W: Write>(stream: &mut W, buf: &[u8]) -> std::io::Result<()> {
+    let mut done = 0;
+    let mut stalls = 0;
+    while done < buf.len() {
+        match stream.write(&buf[done..]) {
+            Ok(0) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::WriteZero,
+                    format!(
+                        "peer stopped accepting data after {done} of {} bytes",
+                        buf.len()
+                    ),
+                ));
+            }
+            Ok(n) => {
+                done += n;
+                stalls = 0;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) if is_stall(&e) && stalls < MAX_STALLS => stalls += 1,
+            Err(e) => return Err(e),
+        }
+    }
+    stream.flush()
+}
+
+// Comme read_exact, en distinguant la fermeture du pair (EOF) d'un simple délai dépassé
+fn read_full<R: Read>(stream: &mut R, buf: &mut [u8], what: &str) -> std::io::Result<()> {
+    let mut done = 0;
+    let mut stalls = 0;
+    while done < buf.len() {
+        match stream.read(&mut buf[done..]) {
+            Ok(0) => {
+                let msg = if done == 0 {
+                    format!("peer closed the connection before the {what}")
+                } else {
+                    format!(
+                        "peer closed the connection after {done} of {} bytes of the {what}",
+                        buf.len()
+                    )
+                };
+                return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, msg));
+            }
+            Ok(n) => {
+                done += n;
+                stalls = 0;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) if is_stall(&e) && stalls < MAX_STALLS => stalls += 1,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+// Lit et jette `len` octets par blocs, sans allouer la trame entière
+fn discard<R: Read>(stream: &mut R, mut len: usize) -> std::io::Result<()> {
+    let mut chunk = [0u8; 8192];
+    while len > 0 {
+        let n = len.min(chunk.len());
+        read_full(stream, &mut chunk[..n], "discarded frame")?;
+        len -= n;
+    }
+    Ok(())
+}
+
+// Format paddé: [longueur réelle u32 BE][données][zéros] arrondi au multiple de `block`
+fn pad_plaintext(plain: &[u8], block: u32) -> Result<Vec<u8>, SessionError> {
+    let block = block.max(1) as usize;
+    let framed = plain.len() + 4;
+    let total = framed.div_ceil(block) * block;
+    let real_len: u32 = plain.len().try_into().map_err(|_| SessionError::TooLarge {
+        len: plain.len() as u64,
+        action: "not sent",
+    })?;
+
+    let mut out = Vec::with_capacity(total);
+    out.extend_from_slice(&real_len.to_be_bytes());
+    out.extend_from_slice(plain);
+    out.resize(total, 0);
+    Ok(out)
+}
+
+fn unpad_plaintext(mut padded: Vec<u8>) -> Result<Vec<u8>, SessionError> {
+    if padded.len() < 4 {
+        return Err(SessionError::Protocol(
+            "padded message too short".to_string(),
+        ));
+    }
+    let real_len = u32::from_be_bytes([padded[0], padded[1], padded[2], padded[3]]) as usize;
+    if real_len > padded.len() - 4 {
+        return Err(SessionError::Protocol(
+            "padded message length out of range".to_string(),
+        ));
+    }
+    padded.drain(..4);
+    padded.truncate(real_len);
+    Ok(padded)
+}
+
+/// A keystream generator: seeded from a 64-bit direction key, its bytes are
+/// XORed over each frame. Each frame starts from `for_frame(seq)`, so
+/// implementations only need to be deterministic.
+trait KeystreamCipher: Clone {
+    fn new(seed: u64) -> Self;
+    fn next_byte(&mut self) -> u8;
+
+    /// Stream for frame `seq`, derived from the seeded state; two frames must
+    /// never share a stream.
+    fn for_frame(&self, seq: u64) -> Self;
+}
+
+/// The stream selected by `--cipher`, as stored in the session keys.
 #[derive(Clone)]
-struct Keystream {
-    state: u32,
+enum Keystream {
+    XorLcg(XorLcg),
+    ChaCha20(ChaCha20),
 }
 
 impl Keystream {
+    fn with_cipher(cipher: Cipher, seed: u64) -> Self {
+        match cipher {
+            Cipher::XorLcg => Keystream::XorLcg(XorLcg::new(seed)),
+            Cipher::ChaCha20 => Keystream::ChaCha20(ChaCha20::new(seed)),
+        }
+    }
+}
+
+impl KeystreamCipher for Keystream {
+    fn new(seed: u64) -> Self {
+        Self::with_cipher(Cipher::default(), seed)
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        match self {
+            Keystream::XorLcg(ks) => ks.next_byte(),
+            Keystream::ChaCha20(ks) => ks.next_byte(),
+        }
+    }
+
+    fn for_frame(&self, seq: u64) -> Self {
+        match self {
+            Keystream::XorLcg(ks) => Keystream::XorLcg(ks.for_frame(seq)),
+            Keystream::ChaCha20(ks) => Keystream::ChaCha20(ks.for_frame(seq)),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct XorLcg {
+    state: u32,
+}
+
+impl KeystreamCipher for XorLcg {
     fn new(seed: u64) -> Self {
         // Fold seed into 32-bit state (non-zero preferred)
         let folded = (seed as u32) ^ ((seed >> 32) as u32);
@@ -316,6 +1287,95 @@ impl Keystream {
         self.state = self.state.wrapping_mul(A).wrapping_add(C);
         (self.state >> 24) as u8
     }
+
+    // Le numéro de trame est mêlé à l'état: jamais deux fois le même flux
+    fn for_frame(&self, seq: u64) -> Self {
+        let mixed = mix64(seq);
+        Self {
+            state: self.state ^ (mixed as u32) ^ ((mixed >> 32) as u32),
+        }
+    }
+}
+
+/// ChaCha20 block function (RFC 8439) used as a byte-at-a-time keystream.
+#[derive(Clone)]
+struct ChaCha20 {
+    state: [u32; 16],
+    block: [u8; 64],
+    pos: usize,
+}
+
+impl ChaCha20 {
+    fn from_key(key: &[u32; 8], counter: u32, nonce: &[u32; 3]) -> Self {
+        let mut state = [0u32; 16];
+        // "expand 32-byte k"
+        state[..4].copy_from_slice(&[0x6170_7865, 0x3320_646E, 0x7962_2D32, 0x6B20_6574]);
+        state[4..12].copy_from_slice(key);
+        state[12] = counter;
+        state[13..].copy_from_slice(nonce);
+        Self {
+            state,
+            block: [0; 64],
+            pos: 64,
+        }
+    }
+
+    fn refill(&mut self) {
+        let mut x = self.state;
+        for _ in 0..10 {
+            quarter_round(&mut x, 0, 4, 8, 12);
+            quarter_round(&mut x, 1, 5, 9, 13);
+            quarter_round(&mut x, 2, 6, 10, 14);
+            quarter_round(&mut x, 3, 7, 11, 15);
+            quarter_round(&mut x, 0, 5, 10, 15);
+            quarter_round(&mut x, 1, 6, 11, 12);
+            quarter_round(&mut x, 2, 7, 8, 13);
+            quarter_round(&mut x, 3, 4, 9, 14);
+        }
+        for (i, (w, s)) in x.iter().zip(&self.state).enumerate() {
+            self.block[4 * i..4 * i + 4].copy_from_slice(&w.wrapping_add(*s).to_le_bytes());
+        }
+        self.state[12] = self.state[12].wrapping_add(1);
+        self.pos = 0;
+    }
+}
+
+impl KeystreamCipher for ChaCha20 {
+    // Clé de 256 bits dérivée de la graine: la sécurité reste bornée par le DH 64 bits
+    fn new(seed: u64) -> Self {
+        let mut key = [0u32; 8];
+        for (i, pair) in key.chunks_mut(2).enumerate() {
+            let word = mix64(seed ^ (i as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            pair[0] = word as u32;
+            pair[1] = (word >> 32) as u32;
+        }
+        Self::from_key(&key, 0, &[0; 3])
+    }
+
+    fn next_byte(&mut self) -> u8 {
+        if self.pos == self.block.len() {
+            self.refill();
+        }
+        self.pos += 1;
+        self.block[self.pos - 1]
+    }
+
+    // Nonce 96 bits = numéro de trame, compteur de blocs remis à zéro
+    fn for_frame(&self, seq: u64) -> Self {
+        let key: [u32; 8] = self.state[4..12].try_into().unwrap();
+        Self::from_key(&key, 0, &[0, seq as u32, (seq >> 32) as u32])
+    }
+}
+
+fn quarter_round(x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(16);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(12);
+    x[a] = x[a].wrapping_add(x[b]);
+    x[d] = (x[d] ^ x[a]).rotate_left(8);
+    x[c] = x[c].wrapping_add(x[d]);
+    x[b] = (x[b] ^ x[c]).rotate_left(7);
 }
 
 fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
@@ -341,6 +1401,20 @@ fn modexp(mut base: u64, mut exp: u64, modulus: u64) -> u64 {
     result
 }
 
+// PSK optionnelle: une valeur différente des deux côtés fait échouer la preuve
+fn mix_psk(secret: u64, psk: Option<&str>) -> u64 {
+    match psk {
+        Some(psk) => mix64(secret ^ fnv1a64(psk.as_bytes())),
+        None => secret,
+    }
+}
+
+fn fnv1a64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
 // SplitMix64-style mixer (fast, deterministic)
 fn mix64(mut x: u64) -> u64 {
     x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
@@ -375,3 +1449,427 @@ enum AppError {
     Cli(String),
     Runtime(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn padded_round_trip() {
+        for block in [1u32, 7, 16, 64] {
+            for len in [0usize, 1, 3, 4, 15, 16, 17, 100] {
+                let plain: Vec<u8> = (0..len).map(|i| i as u8).collect();
+                let ks = Keystream::new(0x1234_5678_9ABC_DEF0);
+
+                let mut wire = Vec::new();
+                send_msg(&mut wire, &ks, 0, &plain, Some(block)).unwrap();
+                let frame_len = u32::from_be_bytes(wire[..4].try_into().unwrap());
+                let padded_len = frame_len - SEQ_LEN as u32;
+                assert_eq!(padded_len % block, 0, "block={block} len={len}");
+                assert_eq!(wire.len(), 4 + frame_len as usize);
+
+                let got = recv_msg(&mut Cursor::new(wire), &ks, 0, Some(block)).unwrap();
+                assert_eq!(got, plain, "block={block} len={len}");
+            }
+        }
+    }
+
+    #[test]
+    fn unpadded_round_trip() {
+        let ks = Keystream::new(42);
+        let mut wire = Vec::new();
+        send_msg(&mut wire, &ks, 0, b"Hello", None).unwrap();
+        assert_eq!(wire.len(), 4 + SEQ_LEN + 5);
+        let got = recv_msg(&mut Cursor::new(wire), &ks, 0, None).unwrap();
+        assert_eq!(got, b"Hello");
+    }
+
+    #[test]
+    fn rekey_stays_in_lockstep() {
+        let secret = 0x0123_4567_89AB_CDEF;
+        let mut server = Keys::new(secret, Role::Server, Cipher::default());
+        let mut client = Keys::new(secret, Role::Client, Cipher::default());
+        let rekey = Some(2);
+
+        let mut wire = Vec::new();
+        let mut streams = Vec::new();
+        for i in 0..5u8 {
+            streams.push(server.send.clone());
+            send_msg(
+                &mut wire,
+                &server.send,
+                server.sent,
+                &[b'm', b'0' + i],
+                None,
+            )
+            .unwrap();
+            server.after_send(rekey);
+        }
+
+        let mut cursor = Cursor::new(wire);
+        for i in 0..5u8 {
+            let got = recv_msg(&mut cursor, &client.recv, client.received, None).unwrap();
+            assert_eq!(got, [b'm', b'0' + i], "message {i}");
+            client.after_recv(rekey);
+        }
+
+        // Nouvelle clé aux messages 2 et 4, identique entre les deux
+        let first = |ks: &Keystream| ks.clone().next_byte();
+        assert_eq!(first(&streams[0]), first(&streams[1]));
+        assert_ne!(first(&streams[1]), first(&streams[2]));
+        assert_ne!(first(&streams[2]), first(&streams[4]));
+        assert_eq!(first(&streams[2]), first(&streams[3]));
+    }
+
+    #[test]
+    fn identical_plaintexts_get_distinct_ciphertexts() {
+        for cipher in [Cipher::XorLcg, Cipher::ChaCha20] {
+            let ks = Keystream::with_cipher(cipher, 7);
+            let mut first = Vec::new();
+            let mut second = Vec::new();
+            send_msg(&mut first, &ks, 0, b"same message", None).unwrap();
+            send_msg(&mut second, &ks, 1, b"same message", None).unwrap();
+            let body = 4 + SEQ_LEN;
+            assert_ne!(first[body..], second[body..], "{cipher:?}");
+            assert_eq!(
+                recv_msg(&mut Cursor::new(second), &ks, 1, None).unwrap(),
+                b"same message"
+            );
+        }
+    }
+
+    #[test]
+    fn out_of_order_frames_are_rejected() {
+        let ks = Keystream::new(7);
+        let frames: Vec<Vec<u8>> = (0..3u64)
+            .map(|seq| {
+                let mut wire = Vec::new();
+                send_msg(&mut wire, &ks, seq, b"frame", None).unwrap();
+                wire
+            })
+            .collect();
+
+        // Ordre 0, 2: la trame 1 manque
+        let mut stream = Cursor::new([frames[0].clone(), frames[2].clone()].concat());
+        assert_eq!(recv_msg(&mut stream, &ks, 0, None).unwrap(), b"frame");
+        let err = recv_msg(&mut stream, &ks, 1, None).unwrap_err();
+        let SessionError::OutOfOrder(seq) = err else {
+            panic!("expected a sequence error, got {err}");
+        };
+        assert_eq!((seq.expected, seq.got), (1, 2));
+
+        // Rejeu de la trame 0 à la place de la 1
+        let mut stream = Cursor::new([frames[0].clone(), frames[0].clone()].concat());
+        recv_msg(&mut stream, &ks, 0, None).unwrap();
+        let err = recv_msg(&mut stream, &ks, 1, None).unwrap_err();
+        assert!(err.to_string().contains("replayed"), "{err}");
+    }
+
+    #[test]
+    fn oversized_frames_are_skipped_or_refused() {
+        let ks = Keystream::new(11);
+        let mut good = Vec::new();
+        send_msg(&mut good, &ks, 0, b"after", None).unwrap();
+
+        // Trame trop grande mais sous MAX_DISCARD: vidée, la suivante reste lisible
+        let len = MAX_MSG_LEN + SEQ_LEN as u32 + 1;
+        let mut wire = len.to_be_bytes().to_vec();
+        wire.resize(4 + len as usize, 0xAA);
+        wire.extend_from_slice(&good);
+        let mut stream = Cursor::new(wire);
+        let err = recv_msg(&mut stream, &ks, 0, None).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                SessionError::TooLarge {
+                    action: "discarded",
+                    ..
+                }
+            ),
+            "{err}"
+        );
+        assert_eq!(recv_msg(&mut stream, &ks, 0, None).unwrap(), b"after");
+
+        // Longueur absurde: rien n'est lu après le préfixe
+        let mut wire = u32::MAX.to_be_bytes().to_vec();
+        wire.extend_from_slice(&good);
+        let mut stream = Cursor::new(wire);
+        let err = recv_msg(&mut stream, &ks, 0, None).unwrap_err();
+        assert!(err.to_string().contains("closing"), "{err}");
+        assert_eq!(stream.position(), 4);
+
+        // Corps tronqué pendant le vidage: fin de flux nette
+        let mut wire = len.to_be_bytes().to_vec();
+        wire.resize(100, 0);
+        let err = recv_msg(&mut Cursor::new(wire), &ks, 0, None).unwrap_err();
+        assert_eq!(err.io_kind(), Some(std::io::ErrorKind::UnexpectedEof));
+    }
+
+    // Flux lent: au plus `chunk` octets par appel, et un WouldBlock un appel sur deux
+    struct Trickle<S> {
+        inner: S,
+        chunk: usize,
+        calls: usize,
+    }
+
+    impl<S> Trickle<S> {
+        fn new(inner: S, chunk: usize) -> Self {
+            Self {
+                inner,
+                chunk,
+                calls: 0,
+            }
+        }
+
+        fn stall(&mut self) -> bool {
+            self.calls += 1;
+            self.calls.is_multiple_of(2)
+        }
+    }
+
+    impl<S: Read> Read for Trickle<S> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.stall() {
+                return Err(std::io::ErrorKind::WouldBlock.into());
+            }
+            let n = buf.len().min(self.chunk);
+            self.inner.read(&mut buf[..n])
+        }
+    }
+
+    impl<S: Write> Write for Trickle<S> {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.stall() {
+                return Err(std::io::ErrorKind::TimedOut.into());
+            }
+            let n = buf.len().min(self.chunk);
+            self.inner.write(&buf[..n])
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    #[test]
+    fn partial_transfers_resume() {
+        let ks = Keystream::new(99);
+        let plain: Vec<u8> = (0..200).map(|i| i as u8).collect();
+
+        let mut out = Trickle::new(Vec::new(), 3);
+        send_msg(&mut out, &ks, 0, &plain, Some(16)).unwrap();
+        send_msg(&mut out, &ks, 1, b"second", None).unwrap();
+
+        let mut reference = Vec::new();
+        send_msg(&mut reference, &ks, 0, &plain, Some(16)).unwrap();
+        send_msg(&mut reference, &ks, 1, b"second", None).unwrap();
+        assert_eq!(out.inner, reference);
+
+        let mut input = Trickle::new(Cursor::new(out.inner), 1);
+        assert_eq!(recv_msg(&mut input, &ks, 0, Some(16)).unwrap(), plain);
+        assert_eq!(recv_msg(&mut input, &ks, 1, None).unwrap(), b"second");
+
+        // Fin de flux entre deux trames: EOF franc
+        let err = recv_msg(&mut input, &ks, 2, None).unwrap_err();
+        assert_eq!(err.io_kind(), Some(std::io::ErrorKind::UnexpectedEof));
+        assert!(
+            err.to_string().contains("before the length prefix"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn eof_mid_frame_and_dead_peer_are_errors() {
+        let ks = Keystream::new(5);
+        let mut wire = Vec::new();
+        send_msg(&mut wire, &ks, 0, b"truncated", None).unwrap();
+        wire.truncate(wire.len() - 3);
+        let err = recv_msg(&mut Cursor::new(wire), &ks, 0, None).unwrap_err();
+        assert_eq!(err.io_kind(), Some(std::io::ErrorKind::UnexpectedEof));
+        assert!(err.to_string().contains("of the frame body"), "{err}");
+
+        // Pair muet: on abandonne après MAX_STALLS délais sans progrès
+        struct Silent;
+        impl Read for Silent {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::WouldBlock.into())
+            }
+        }
+        let err = recv_msg(&mut Silent, &ks, 0, None).unwrap_err();
+        assert_eq!(err.io_kind(), Some(std::io::ErrorKind::WouldBlock));
+    }
+
+    // Handshake complet sur une vraie socket locale, chaque côté avec sa PSK et son chiffrement
+    fn handshake_pair(
+        server_psk: Option<&'static str>,
+        client_psk: Option<&'static str>,
+        proof: bool,
+        (server_cipher, client_cipher): (Cipher, Cipher),
+    ) -> (Result<Keys, SessionError>, Result<Keys, SessionError>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            configure_stream(&mut stream).unwrap();
+            dh_handshake(
+                &mut Wire::new(&mut stream, false),
+                Role::Server,
+                server_psk,
+                proof,
+                server_cipher,
+            )
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        configure_stream(&mut stream).unwrap();
+        let client = dh_handshake(
+            &mut Wire::new(&mut stream, false),
+            Role::Client,
+            client_psk,
+            proof,
+            client_cipher,
+        );
+        (server.join().unwrap(), client)
+    }
+
+    #[test]
+    fn psk_must_match() {
+        let (server, client) = handshake_pair(
+            Some("s3cret"),
+            Some("s3cret"),
+            true,
+            (Cipher::ChaCha20, Cipher::ChaCha20),
+        );
+        assert_eq!(server.unwrap().fingerprint, client.unwrap().fingerprint);
+
+        for (server_psk, client_psk) in [(Some("s3cret"), Some("other")), (Some("s3cret"), None)] {
+            let (server, client) = handshake_pair(
+                server_psk,
+                client_psk,
+                true,
+                (Cipher::ChaCha20, Cipher::ChaCha20),
+            );
+            for res in [server, client] {
+                let err = res.err().expect("handshake should fail");
+                assert!(matches!(err, SessionError::Handshake(_)), "{err}");
+                assert!(
+                    err.to_string().contains("secret verification failed"),
+                    "{err}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn no_proof_peers_still_talk() {
+        let (server, client) =
+            handshake_pair(None, None, false, (Cipher::ChaCha20, Cipher::ChaCha20));
+        let (server, client) = (server.unwrap(), client.unwrap());
+        assert_eq!(server.fingerprint, client.fingerprint);
+        let mut wire = Vec::new();
+        send_msg(&mut wire, &server.send, 0, b"Hello", None).unwrap();
+        let got = recv_msg(&mut Cursor::new(wire), &client.recv, 0, None).unwrap();
+        assert_eq!(got, b"Hello");
+
+        // Sans preuve, un secret différent passe la poignée de main mais le premier message est illisible
+        let (server, client) = handshake_pair(
+            Some("s3cret"),
+            Some("other"),
+            false,
+            (Cipher::ChaCha20, Cipher::ChaCha20),
+        );
+        let (server, client) = (server.unwrap(), client.unwrap());
+        let mut wire = Vec::new();
+        send_msg(&mut wire, &server.send, 0, b"Hello", None).unwrap();
+        let got = recv_msg(&mut Cursor::new(wire), &client.recv, 0, None).unwrap();
+        assert_ne!(got, b"Hello");
+    }
+
+    #[test]
+    fn no_proof_speaks_textbook_dh() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            configure_stream(&mut stream).unwrap();
+            dh_handshake(
+                &mut Wire::new(&mut stream, false),
+                Role::Server,
+                None,
+                false,
+                Cipher::XorLcg,
+            )
+        });
+
+        // Pair DH minimal: clés publiques seules, sans hello ni preuve
+        let mut stream = TcpStream::connect(addr).unwrap();
+        configure_stream(&mut stream).unwrap();
+        let mut buf = [0u8; 8];
+        stream.read_exact(&mut buf).unwrap();
+        let private = 123_456_789;
+        stream
+            .write_all(&modexp(G, private, P).to_be_bytes())
+            .unwrap();
+        let secret = mix_psk(modexp(u64::from_be_bytes(buf), private, P), None);
+
+        let server = server.join().unwrap().unwrap();
+        let ours = Keys::new(secret, Role::Client, Cipher::XorLcg);
+        assert_eq!(server.fingerprint, ours.fingerprint);
+    }
+
+    #[test]
+    fn chacha20_matches_rfc_8439() {
+        // Section 2.3.2: clé 00..1f, nonce 00:00:00:09:00:00:00:4a:00:00:00:00, compteur 1
+        let key: Vec<u32> = (0..8u32)
+            .map(|i| {
+                u32::from_le_bytes([
+                    4 * i as u8,
+                    4 * i as u8 + 1,
+                    4 * i as u8 + 2,
+                    4 * i as u8 + 3,
+                ])
+            })
+            .collect();
+        let mut ks =
+            ChaCha20::from_key(&key.try_into().unwrap(), 1, &[0x0900_0000, 0x4A00_0000, 0]);
+        let head: Vec<u8> = (0..16).map(|_| ks.next_byte()).collect();
+        assert_eq!(
+            head,
+            [
+                0x10, 0xf1, 0xe7, 0xe4, 0xd1, 0x3b, 0x59, 0x15, 0x50, 0x0f, 0xdd, 0x1f, 0xa3, 0x20,
+                0x71, 0xc4
+            ]
+        );
+    }
+
+    #[test]
+    fn every_cipher_establishes_a_channel() {
+        for cipher in [Cipher::XorLcg, Cipher::ChaCha20] {
+            let (server, client) = handshake_pair(None, None, true, (cipher, cipher));
+            let (mut server, client) = (server.unwrap(), client.unwrap());
+            let mut wire = Vec::new();
+            // Deux trames: la seconde traverse une frontière de bloc ChaCha20
+            send_msg(&mut wire, &server.send, 0, b"Hello", None).unwrap();
+            server.after_send(None);
+            send_msg(&mut wire, &server.send, 1, &[7u8; 100], None).unwrap();
+            let mut stream = Cursor::new(wire);
+            assert_eq!(
+                recv_msg(&mut stream, &client.recv, 0, None).unwrap(),
+                b"Hello"
+            );
+            assert_eq!(
+                recv_msg(&mut stream, &client.recv, 1, None).unwrap(),
+                [7u8; 100]
+            );
+        }
+
+        // Chiffrements différents: refusé des deux côtés avant l'échange DH
+        let (server, client) = handshake_pair(None, None, true, (Cipher::XorLcg, Cipher::ChaCha20));
+        for res in [server, client] {
+            let err = res.err().expect("handshake should fail");
+            assert!(matches!(err, SessionError::Handshake(_)), "{err}");
+            assert!(err.to_string().contains("cipher mismatch"), "{err}");
+        }
+    }
+}
@@ -0,0 +1,2274 @@
+//! Grille hexadécimale et solveurs de chemins utilisés par le binaire `hexpath`.
+
+use rand::Rng;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet, VecDeque};
+use std::io::{BufRead, Read, Write};
+use std::time::{Duration, Instant};
+
+pub const MAX_SIDE: usize = 512;
+pub const MAX_CELLS: usize = MAX_SIDE * MAX_SIDE;
+
+/*GRID / PARSING*/
+
+/// Rectangular grid of byte costs stored row-major.
+#[derive(Clone, Debug)]
+pub struct Grid {
+    pub w: usize,
+    pub h: usize,
+    pub cells: Vec<u8>,
+    /// How the solvers weigh and restrict moves on this grid.
+    pub opts: SolveOptions,
+}
+
+/// Solver settings carried by a `Grid`; the default is a plain, unlimited solve.
+#[derive(Clone, Debug)]
+pub struct SolveOptions {
+    /// Terrain bands applied to cell values when used as weights.
+    pub bands: Vec<Band>,
+    /// Cells whose value exceeds this are impassable (start and goal excepted).
+    pub max_cell: Option<u8>,
+    /// Wall-clock budget for each solve; past it, solvers fail with `SolveError::TimeLimit`.
+    pub time_limit: Option<Duration>,
+    /// Multiplier on the entered cell's weight, indexed by `Direction` (up, down, left, right).
+    pub dir_cost: [u64; 4],
+    /// Cells forbidden outright, like obstacles given on the command line.
+    pub avoid: HashSet<(usize, usize)>,
+}
+
+impl Default for SolveOptions {
+    fn default() -> Self {
+        SolveOptions {
+            bands: Vec::new(),
+            max_cell: None,
+            time_limit: None,
+            dir_cost: [1; 4],
+            avoid: HashSet::new(),
+        }
+    }
+}
+
+/// Cost multiplier for cells whose value lies in `[lo, hi]`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Band {
+    pub lo: u8,
+    pub hi: u8,
+    pub mult: u64,
+}
+
+impl std::str::FromStr for Band {
+    type Err = String;
+
+    /// `LO:HI:MULT`, LO/HI in hex like map cells (e.g. `80:FF:2`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let [lo, hi, mult] = parts[..] else {
+            return Err(format!(
+                "invalid band '{s}' (expected LO:HI:MULT, e.g. 80:FF:2)"
+            ));
+        };
+        let hex = |t: &str| {
+            let t = t.trim();
+            let t = t
+                .strip_prefix("0x")
+                .or_else(|| t.strip_prefix("0X"))
+                .unwrap_or(t);
+            u8::from_str_radix(t, 16)
+                .map_err(|_| format!("invalid band bound '{t}' (expected 00-FF)"))
+        };
+        let lo = hex(lo)?;
+        let hi = hex(hi)?;
+        if lo > hi {
+            return Err(format!("invalid band '{s}' (LO > HI)"));
+        }
+        let mult = mult
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid band multiplier '{mult}'"))?;
+        Ok(Band { lo, hi, mult })
+    }
+}
+
+/// Weight of a cell value: the first band containing it multiplies it.
+pub fn effective_cost(value: u8, bands: &[Band]) -> u64 {
+    match bands.iter().find(|b| (b.lo..=b.hi).contains(&value)) {
+        Some(b) => (value as u64).saturating_mul(b.mult),
+        None => value as u64,
+    }
+}
+
+impl Grid {
+    /// Grid of `w * h` row-major `cells` with default solver options.
+    pub fn new(w: usize, h: usize, cells: Vec<u8>) -> Grid {
+        Grid {
+            w,
+            h,
+            cells,
+            opts: SolveOptions::default(),
+        }
+    }
+
+    pub fn idx(&self, x: usize, y: usize) -> Option<usize> {
+        if x < self.w && y < self.h {
+            Some(y * self.w + x)
+        } else {
+            None
+        }
+    }
+
+    pub fn at(&self, x: usize, y: usize) -> Option<u8> {
+        self.idx(x, y).and_then(|i| self.cells.get(i).copied())
+    }
+
+    /// Cost of entering `(x, y)` once terrain bands are applied.
+    pub fn weight(&self, x: usize, y: usize) -> u64 {
+        effective_cost(self.at(x, y).unwrap_or(0), &self.opts.bands)
+    }
+
+    /// Cost of the move `from -> to`: the weight of `to` times the multiplier
+    /// of the move's direction.
+    pub fn step_weight(&self, from: (usize, usize), to: (usize, usize)) -> u64 {
+        let mult = self.opts.dir_cost[step_direction(from, to) as usize];
+        self.weight(to.0, to.1).saturating_mul(mult)
+    }
+
+    /// Signed cost of the move `from -> to`: the value of `to` read as an `i8`
+    /// (0x80..=0xFF are bonuses), times the multiplier of the move's direction.
+    pub fn signed_step_weight(&self, from: (usize, usize), to: (usize, usize)) -> i64 {
+        let mult = self.opts.dir_cost[step_direction(from, to) as usize] as i64;
+        (self.at(to.0, to.1).unwrap_or(0) as i8 as i64).saturating_mul(mult)
+    }
+
+    /// False for cells above `max_cell` or listed in `avoid`; the start and
+    /// goal always stay open.
+    pub fn passable(&self, x: usize, y: usize) -> bool {
+        if self.opts.max_cell.is_none() && self.opts.avoid.is_empty() {
+            return true;
+        }
+        if (x, y) == (0, 0) || (x, y) == (self.w - 1, self.h - 1) {
+            return true;
+        }
+        if self.opts.avoid.contains(&(x, y)) {
+            return false;
+        }
+        self.opts
+            .max_cell
+            .is_none_or(|limit| self.at(x, y).is_some_and(|v| v <= limit))
+    }
+
+    /// Number of cells made impassable by `max_cell` (start and goal excepted);
+    /// cells listed in `avoid` are not counted.
+    pub fn excluded_cells(&self) -> usize {
+        let Some(limit) = self.opts.max_cell else {
+            return 0;
+        };
+        let last = self.cells.len() - 1;
+        self.cells
+            .iter()
+            .enumerate()
+            .filter(|&(i, &v)| i != 0 && i != last && v > limit)
+            .count()
+    }
+
+    /// Copy with every cell replaced by `FF - value`: valleys become ridges.
+    ///
+    /// The corners swap too (start `FF`, goal `00`), so validate before inverting.
+    pub fn inverted(&self) -> Grid {
+        Grid {
+            cells: self.cells.iter().map(|v| 0xFF - v).collect(),
+            ..self.clone()
+        }
+    }
+
+    /// `neighbors4` restricted to passable cells; used by every solver.
+    pub fn open_neighbors(&self, x: usize, y: usize) -> Vec<(usize, usize)> {
+        let mut out = neighbors4(x, y, self.w, self.h);
+        out.retain(|&(nx, ny)| self.passable(nx, ny));
+        out
+    }
+}
+
+/// Parses a `WxH` size spec (e.g. `10x10`).
+pub fn parse_wh(s: &str) -> Result<(usize, usize), String> {
+    let s = s.trim();
+    let (w_s, h_s) = s
+        .split_once('x')
+        .or_else(|| s.split_once('X'))
+        .ok_or_else(|| format!("invalid size '{s}' (expected WxH, e.g. 10x10)"))?;
+    let w: usize = w_s
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid width in '{s}'"))?;
+    let h: usize = h_s
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid height in '{s}'"))?;
+    if w == 0 || h == 0 {
+        return Err("width and height must be > 0".to_string());
+    }
+    if w > MAX_SIDE || h > MAX_SIDE || w * h > MAX_CELLS {
+        return Err("grid too large".to_string());
+    }
+    Ok((w, h))
+}
+
+/// How `generate_grid_with` fills the cells.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Distribution {
+    /// Independent uniform bytes (pure noise).
+    #[default]
+    Uniform,
+    /// Values rise toward the bottom-right, with some noise.
+    Gradient,
+    /// Smooth blobs of similar values (interpolated coarse lattice).
+    Clustered,
+}
+
+impl std::str::FromStr for Distribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "uniform" => Ok(Distribution::Uniform),
+            "gradient" => Ok(Distribution::Gradient),
+            "clustered" => Ok(Distribution::Clustered),
+            _ => Err(format!(
+                "invalid distribution '{s}' (expected uniform, gradient or clustered)"
+            )),
+        }
+    }
+}
+
+/// Random uniform grid with `00` at the start and `FF` at the goal.
+pub fn generate_grid(w: usize, h: usize) -> Grid {
+    generate_grid_with(w, h, Distribution::Uniform, &mut rand::thread_rng())
+}
+
+/// Random grid drawn from `dist`, with `00` at the start and `FF` at the goal.
+pub fn generate_grid_with<R: Rng + ?Sized>(
+    w: usize,
+    h: usize,
+    dist: Distribution,
+    rng: &mut R,
+) -> Grid {
+    let mut cells = match dist {
+        Distribution::Uniform => fill_uniform(w, h, rng),
+        Distribution::Gradient => fill_gradient(w, h, rng),
+        Distribution::Clustered => fill_clustered(w, h, rng),
+    };
+
+    // Contraintes : 00 (top-left), FF (bottom-right)
+    if let Some(first) = cells.first_mut() {
+        *first = 0x00;
+    }
+    if let Some(last) = cells.last_mut() {
+        *last = 0xFF;
+    }
+    Grid::new(w, h, cells)
+}
+
+/// Carves a random monotone (right/down) path from the start to the goal whose
+/// interior cells are all `<= limit`, so the map stays solvable under
+/// `max_cell = Some(limit)`. Corners keep their `00`/`FF` values.
+pub fn carve_monotone_path<R: Rng + ?Sized>(
+    grid: &mut Grid,
+    limit: u8,
+    rng: &mut R,
+) -> Vec<(usize, usize)> {
+    let (mut x, mut y) = (0usize, 0usize);
+    let mut path = vec![(0, 0)];
+    while (x, y) != (grid.w - 1, grid.h - 1) {
+        // Droite ou bas au hasard, proportionnellement au chemin restant
+        let right_left = grid.w - 1 - x;
+        let down_left = grid.h - 1 - y;
+        if rng.gen_range(0..right_left + down_left) < right_left {
+            x += 1;
+        } else {
+            y += 1;
+        }
+        path.push((x, y));
+    }
+
+    for &(x, y) in &path[1..path.len() - 1] {
+        if let Some(i) = grid.idx(x, y)
+            && grid.cells[i] > limit
+        {
+            grid.cells[i] = rng.gen_range(0..=limit);
+        }
+    }
+    path
+}
+
+fn fill_uniform<R: Rng + ?Sized>(w: usize, h: usize, rng: &mut R) -> Vec<u8> {
+    (0..(w * h))
+        .map(|_| (rng.next_u32() & 0xFF) as u8)
+        .collect()
+}
+
+// Rampe diagonale 0 -> 255 plus un bruit de +/- 48
+fn fill_gradient<R: Rng + ?Sized>(w: usize, h: usize, rng: &mut R) -> Vec<u8> {
+    let span = (w + h).saturating_sub(2).max(1) as f64;
+    let mut cells = Vec::with_capacity(w * h);
+    for y in 0..h {
+        for x in 0..w {
+            let base = (x + y) as f64 / span * 255.0;
+            let noise = rng.gen_range(-48.0..=48.0);
+            cells.push((base + noise).clamp(0.0, 255.0) as u8);
+        }
+    }
+    cells
+}
+
+// Bruit de valeur: treillis grossier aléatoire interpolé (smoothstep) + léger bruit
+fn fill_clustered<R: Rng + ?Sized>(w: usize, h: usize, rng: &mut R) -> Vec<u8> {
+    let step = (w.min(h) / 4).max(2);
+    let lw = w / step + 2;
+    let lh = h / step + 2;
+    let lattice: Vec<f64> = (0..(lw * lh)).map(|_| rng.gen_range(0.0..=255.0)).collect();
+    let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+
+    let mut cells = Vec::with_capacity(w * h);
+    for y in 0..h {
+        for x in 0..w {
+            let (gx, gy) = (x / step, y / step);
+            let tx = smooth((x % step) as f64 / step as f64);
+            let ty = smooth((y % step) as f64 / step as f64);
+            let v00 = lattice[gy * lw + gx];
+            let v10 = lattice[gy * lw + gx + 1];
+            let v01 = lattice[(gy + 1) * lw + gx];
+            let v11 = lattice[(gy + 1) * lw + gx + 1];
+            let top = v00 + (v10 - v00) * tx;
+            let bottom = v01 + (v11 - v01) * tx;
+            let noise = rng.gen_range(-16.0..=16.0);
+            cells.push((top + (bottom - top) * ty + noise).clamp(0.0, 255.0) as u8);
+        }
+    }
+    cells
+}
+
+/// Formats the grid as space-separated hex rows (no trailing newline).
+pub fn format_grid(grid: &Grid) -> String {
+    let mut out = String::new();
+    for y in 0..grid.h {
+        for x in 0..grid.w {
+            if x > 0 {
+                out.push(' ');
+            }
+            let v = grid.at(x, y).unwrap_or(0);
+            out.push_str(&format!("{v:02X}"));
+        }
+        if y + 1 < grid.h {
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Parses a map made of whitespace-separated hex bytes, one row per line.
+/// `#` starts a comment running to the end of the line; blank lines and
+/// `---` separator lines are skipped.
+pub fn parse_grid_text(content: &str) -> Result<Grid, String> {
+    let mut rows: Vec<Vec<u8>> = Vec::new();
+
+    for line in content.lines() {
+        let row = parse_row(line)?;
+        if !row.is_empty() {
+            rows.push(row);
+        }
+    }
+
+    grid_from_rows(rows)
+}
+
+/// Parses a map drawn with one character per cell, one row per line:
+///
+/// | char      | value                        |
+/// |-----------|------------------------------|
+/// | `.`       | `00`                         |
+/// | `#`       | `FF`                         |
+/// | `0` - `9` | `digit * 255 / 9` (`9` = FF) |
+///
+/// Whitespace is ignored, so cells may be spaced out; blank lines are skipped.
+pub fn parse_grid_chars(content: &str) -> Result<Grid, String> {
+    let mut rows: Vec<Vec<u8>> = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let row = line
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| {
+                glyph_value(c).ok_or_else(|| {
+                    format!(
+                        "line {}: unknown map character '{c}' (expected . # 0-9)",
+                        i + 1
+                    )
+                })
+            })
+            .collect::<Result<Vec<u8>, String>>()?;
+        if !row.is_empty() {
+            rows.push(row);
+        }
+    }
+
+    grid_from_rows(rows)
+}
+
+fn glyph_value(c: char) -> Option<u8> {
+    match c {
+        '.' => Some(0x00),
+        '#' => Some(0xFF),
+        '0'..='9' => c.to_digit(10).map(|d| (d * 255 / 9) as u8),
+        _ => None,
+    }
+}
+
+// Contrôles communs aux parseurs texte: rectangulaire, non vide, dans les limites
+fn grid_from_rows(rows: Vec<Vec<u8>>) -> Result<Grid, String> {
+    if rows.is_empty() {
+        return Err("empty map".to_string());
+    }
+
+    let w = rows[0].len();
+    if w == 0 {
+        return Err("invalid map width".to_string());
+    }
+    if w > MAX_SIDE {
+        return Err("grid too wide".to_string());
+    }
+    for (i, r) in rows.iter().enumerate() {
+        if r.len() != w {
+            return Err(format!("non-rectangular map at row {i}"));
+        }
+    }
+
+    let h = rows.len();
+    if h > MAX_SIDE || w * h > MAX_CELLS {
+        return Err("grid too large".to_string());
+    }
+
+    let mut cells = Vec::with_capacity(w * h);
+    for r in rows {
+        cells.extend(r);
+    }
+
+    Ok(Grid::new(w, h, cells))
+}
+
+/// Streaming sibling of `parse_grid_text`: parses row by row straight into
+/// the cell buffer, without holding the whole file in memory.
+pub fn parse_grid_reader<R: BufRead>(reader: R) -> Result<Grid, String> {
+    let mut cells: Vec<u8> = Vec::new();
+    let mut w = 0usize;
+    let mut h = 0usize;
+
+    for line in reader.lines() {
+        let line = line.map_err(|e| format!("failed to read map: {e}"))?;
+        let row = parse_row(&line).map_err(|e| format!("row {h}: {e}"))?;
+        if row.is_empty() {
+            continue;
+        }
+
+        if h == 0 {
+            w = row.len();
+            if w > MAX_SIDE {
+                return Err("grid too wide".to_string());
+            }
+        } else if row.len() != w {
+            return Err(format!("non-rectangular map at row {h}"));
+        }
+
+        h += 1;
+        if h > MAX_SIDE || w * h > MAX_CELLS {
+            return Err("grid too large".to_string());
+        }
+        cells.extend_from_slice(&row);
+    }
+
+    if h == 0 {
+        return Err("empty map".to_string());
+    }
+
+    Ok(Grid::new(w, h, cells))
+}
+
+// Une ligne de la map: octets hex séparés par des espaces (virgule/point-virgule tolérés).
+// Le commentaire (# jusqu'à la fin de ligne) est retiré avant découpage; "---" vaut une ligne vide.
+fn parse_row(line: &str) -> Result<Vec<u8>, String> {
+    let line = line.split_once('#').map_or(line, |(cells, _)| cells);
+    if line.trim() == "---" {
+        return Ok(Vec::new());
+    }
+
+    let mut row = Vec::new();
+    for tok in line.split_whitespace() {
+        let t0 = tok.trim().trim_end_matches(',').trim_end_matches(';');
+        let t = t0
+            .strip_prefix("0x")
+            .or_else(|| t0.strip_prefix("0X"))
+            .unwrap_or(t0);
+
+        if t.is_empty() {
+            return Err("empty hex token".to_string());
+        }
+        if t.len() > 2 {
+            return Err(format!("invalid hex token '{tok}' (expected 00-FF)"));
+        }
+        let v = u8::from_str_radix(t, 16)
+            .map_err(|_| format!("invalid hex token '{tok}' (expected 00-FF)"))?;
+        row.push(v);
+    }
+    Ok(row)
+}
+
+/// Cells whose value differs between two maps of the same size, as
+/// `(x, y, value in a, value in b)` in row-major order.
+pub fn diff_grids(a: &Grid, b: &Grid) -> Result<Vec<(usize, usize, u8, u8)>, String> {
+    if (a.w, a.h) != (b.w, b.h) {
+        return Err(format!(
+            "dimensions differ: {}x{} vs {}x{}",
+            a.w, a.h, b.w, b.h
+        ));
+    }
+    Ok(a.cells
+        .iter()
+        .zip(&b.cells)
+        .enumerate()
+        .filter(|(_, (va, vb))| va != vb)
+        .map(|(i, (&va, &vb))| (i % a.w, i / a.w, va, vb))
+        .collect())
+}
+
+/*FORMAT BINAIRE*/
+
+/// Magic bytes opening a binary `.grid` file.
+pub const GRID_MAGIC: [u8; 4] = *b"HXPG";
+
+/// Writes the grid as `GRID_MAGIC`, u32 BE width, u32 BE height, then the raw
+/// row-major cell bytes.
+pub fn write_grid_bin<W: Write>(mut out: W, grid: &Grid) -> std::io::Result<()> {
+    let dim = |v: usize| {
+        u32::try_from(v)
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "grid too large"))
+    };
+    out.write_all(&GRID_MAGIC)?;
+    out.write_all(&dim(grid.w)?.to_be_bytes())?;
+    out.write_all(&dim(grid.h)?.to_be_bytes())?;
+    out.write_all(&grid.cells)?;
+    out.flush()
+}
+
+/// Reads a grid written by `write_grid_bin`, checking the magic, the size caps
+/// and that exactly `w*h` cell bytes follow the header.
+pub fn read_grid_bin<R: Read>(mut input: R) -> Result<Grid, String> {
+    let mut header = [0u8; 12];
+    input
+        .read_exact(&mut header)
+        .map_err(|e| format!("truncated header: {e}"))?;
+    if header[..4] != GRID_MAGIC {
+        return Err("not a binary grid (bad magic)".to_string());
+    }
+    let w = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let h = u32::from_be_bytes([header[8], header[9], header[10], header[11]]) as usize;
+    if w == 0 || h == 0 {
+        return Err("invalid grid dimensions".to_string());
+    }
+    if w > MAX_SIDE || h > MAX_SIDE || w * h > MAX_CELLS {
+        return Err("grid too large".to_string());
+    }
+
+    let mut cells = vec![0u8; w * h];
+    input
+        .read_exact(&mut cells)
+        .map_err(|_| format!("truncated cells (expected {} bytes)", w * h))?;
+    let mut extra = [0u8; 1];
+    if input
+        .read(&mut extra)
+        .map_err(|e| format!("failed to read map: {e}"))?
+        != 0
+    {
+        return Err(format!("trailing bytes after {} cells", w * h));
+    }
+
+    Ok(Grid::new(w, h, cells))
+}
+
+/// Checks storage consistency and the `00` start / `FF` goal convention.
+/// A 1x1 grid, where start and goal coincide, may hold either `00` or `FF`.
+pub fn validate_grid(grid: &Grid) -> Result<(), String> {
+    if grid.w == 0 || grid.h == 0 {
+        return Err("invalid grid dimensions".to_string());
+    }
+    if grid.cells.len() != grid.w * grid.h {
+        return Err("invalid grid storage".to_string());
+    }
+    // 1x1: l'unique case est à la fois départ et arrivée, 00 ou FF convient
+    if grid.w * grid.h == 1 {
+        return match grid.at(0, 0) {
+            Some(0x00 | 0xFF) => Ok(()),
+            _ => Err("single-cell grid must be 00 or FF (start and goal coincide)".to_string()),
+        };
+    }
+    if grid.at(0, 0) != Some(0x00) {
+        return Err("start (top-left) must be 00".to_string());
+    }
+    if grid.at(grid.w - 1, grid.h - 1) != Some(0xFF) {
+        return Err("end (bottom-right) must be FF".to_string());
+    }
+    Ok(())
+}
+
+/*MIN COST (Dijkstra)*/
+
+// Départ == arrivée (grille 1x1): chemin d'une seule case, rien n'est payé
+fn trivial_path() -> (u64, Vec<(usize, usize)>) {
+    (0, vec![(0, 0)])
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+struct State {
+    cost: u64,
+    idx: usize,
+}
+
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .cmp(&self.cost)
+            .then_with(|| other.idx.cmp(&self.idx))
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Why a solver found no answer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SolveError {
+    /// The goal cannot be reached from the start.
+    NoPath,
+    /// The solve ran past `SolveOptions::time_limit`.
+    TimeLimit,
+    /// Signed costs: a reachable cycle of negative total cost, so no minimum exists.
+    NegativeCycle,
+}
+
+impl std::fmt::Display for SolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolveError::NoPath => write!(f, "no path found"),
+            SolveError::TimeLimit => write!(f, "search exceeded time limit"),
+            SolveError::NegativeCycle => write!(f, "negative cycle: signed costs have no minimum"),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+// L'horloge n'est lue qu'une itération sur CHECK_EVERY pour rester négligeable
+struct Watchdog {
+    deadline: Option<Instant>,
+    ticks: u32,
+}
+
+impl Watchdog {
+    const CHECK_EVERY: u32 = 4096;
+
+    fn new(grid: &Grid) -> Self {
+        Watchdog {
+            deadline: grid.opts.time_limit.map(|t| Instant::now() + t),
+            ticks: 0,
+        }
+    }
+
+    fn tick(&mut self) -> Result<(), SolveError> {
+        let Some(deadline) = self.deadline else {
+            return Ok(());
+        };
+        self.ticks += 1;
+        if self.ticks.is_multiple_of(Self::CHECK_EVERY) && Instant::now() >= deadline {
+            return Err(SolveError::TimeLimit);
+        }
+        Ok(())
+    }
+}
+
+/// Solver counters and per-phase wall-clock times, filled by the `*_profiled` variants.
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    pub heap_pops: u64,
+    pub relaxations: u64,
+    pub min_solve: Duration,
+    pub bfs: Duration,
+    pub dp: Duration,
+}
+
+/// Minimum total cost from top-left to bottom-right; entering a cell costs its value.
+pub fn dijkstra_min_cost(grid: &Grid) -> Result<(u64, Vec<(usize, usize)>), SolveError> {
+    min_cost_impl(grid, None, None)
+}
+
+/// `dijkstra_min_cost`, recording heap pops, relaxations and solve time in `prof`.
+pub fn dijkstra_min_cost_profiled(
+    grid: &Grid,
+    prof: &mut Profile,
+) -> Result<(u64, Vec<(usize, usize)>), SolveError> {
+    let started = Instant::now();
+    let res = min_cost_impl(grid, Some(&mut *prof), None);
+    prof.min_solve = started.elapsed();
+    res
+}
+
+/// `dijkstra_min_cost`, calling `on_settle(settled, cell)` each time a cell's
+/// final cost is known; `settled` counts settled cells so far, goal included.
+pub fn dijkstra_min_cost_observed(
+    grid: &Grid,
+    mut on_settle: impl FnMut(usize, (usize, usize)),
+) -> Result<(u64, Vec<(usize, usize)>), SolveError> {
+    min_cost_impl(grid, None, Some(&mut on_settle))
+}
+
+/// Minimum total cost with cells read as signed `i8`, so 0x80..=0xFF lower the
+/// cost. Bellman-Ford over the four-neighbour moves: fails with
+/// `SolveError::NegativeCycle` when a reachable cycle has a negative total.
+pub fn bellman_ford_min_cost(grid: &Grid) -> Result<(i64, Vec<(usize, usize)>), SolveError> {
+    let n = grid.w * grid.h;
+    let goal = n - 1;
+    if goal == 0 {
+        return Ok((0, vec![(0, 0)]));
+    }
+    let mut watchdog = Watchdog::new(grid);
+
+    let mut dist: Vec<Option<i64>> = vec![None; n];
+    let mut prev: Vec<Option<usize>> = vec![None; n];
+    dist[0] = Some(0);
+
+    // n-1 passes au plus, puis une passe de plus: toute relaxation restante trahit un cycle négatif
+    for round in 0..n {
+        let mut changed = false;
+        for idx in 0..n {
+            watchdog.tick()?;
+            let Some(d) = dist[idx] else {
+                continue;
+            };
+            let (x, y) = (idx % grid.w, idx / grid.w);
+            for (nx, ny) in neighbors4(x, y, grid.w, grid.h) {
+                if !grid.passable(nx, ny) {
+                    continue;
+                }
+                let nidx = ny * grid.w + nx;
+                let next = d.saturating_add(grid.signed_step_weight((x, y), (nx, ny)));
+                if dist[nidx].is_none_or(|cur| next < cur) {
+                    if round == n - 1 {
+                        return Err(SolveError::NegativeCycle);
+                    }
+                    dist[nidx] = Some(next);
+                    prev[nidx] = Some(idx);
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+        // Un cycle dans les prédécesseurs est forcément négatif: inutile d'attendre n-1 passes
+        if has_prev_cycle(&prev) {
+            return Err(SolveError::NegativeCycle);
+        }
+    }
+
+    let Some(cost) = dist[goal] else {
+        return Err(SolveError::NoPath);
+    };
+    Ok((cost, reconstruct_path(prev, grid.w, goal)))
+}
+
+// Parcours des chaînes de prédécesseurs, chaque cellule visitée une seule fois
+fn has_prev_cycle(prev: &[Option<usize>]) -> bool {
+    // 0 = pas vue, 1 = chaîne en cours, 2 = terminée
+    let mut state = vec![0u8; prev.len()];
+    for start in 0..prev.len() {
+        let mut cur = Some(start);
+        while let Some(i) = cur {
+            match state[i] {
+                1 => return true,
+                2 => break,
+                _ => {
+                    state[i] = 1;
+                    cur = prev[i];
+                }
+            }
+        }
+        let mut cur = Some(start);
+        while let Some(i) = cur {
+            if state[i] != 1 {
+                break;
+            }
+            state[i] = 2;
+            cur = prev[i];
+        }
+    }
+    false
+}
+
+fn min_cost_impl(
+    grid: &Grid,
+    prof: Option<&mut Profile>,
+    observe: Option<&mut dyn FnMut(usize, (usize, usize))>,
+) -> Result<(u64, Vec<(usize, usize)>), SolveError> {
+    let goal = grid.w * grid.h - 1;
+    if goal == 0 {
+        return Ok(trivial_path());
+    }
+    let (dist, prev) = dijkstra(grid, Some(goal), prof, observe)?;
+
+    if dist[goal] == u64::MAX {
+        return Err(SolveError::NoPath);
+    }
+
+    let path = reconstruct_path(prev, grid.w, goal);
+    Ok((dist[goal], path))
+}
+
+/// Minimum cost from the start to every cell (row-major), `None` when unreachable.
+pub fn distance_map(grid: &Grid) -> Result<Vec<Option<u64>>, SolveError> {
+    let (dist, _) = dijkstra(grid, None, None, None)?;
+    Ok(dist
+        .into_iter()
+        .map(|d| if d == u64::MAX { None } else { Some(d) })
+        .collect())
+}
+
+/// Per-cell remaining cost to the goal and next cell, as built by `goal_distance_map`.
+pub type GoalMap = (Vec<Option<u64>>, Vec<Option<usize>>);
+
+/// Remaining cost from every cell to the goal and the next cell of an optimal
+/// route from it (`None` when the goal is out of reach, or at the goal itself).
+pub fn goal_distance_map(grid: &Grid) -> Result<GoalMap, SolveError> {
+    let n = grid.w * grid.h;
+    let goal = n - 1;
+    let mut watchdog = Watchdog::new(grid);
+
+    let mut dist = vec![u64::MAX; n];
+    let mut next: Vec<Option<usize>> = vec![None; n];
+    let mut heap = BinaryHeap::new();
+
+    dist[goal] = 0;
+    heap.push(State { cost: 0, idx: goal });
+
+    // Dijkstra inversé: passer de v à u coûte le poids de u (et la direction v -> u)
+    while let Some(State { cost, idx }) = heap.pop() {
+        watchdog.tick()?;
+        if cost != dist[idx] {
+            continue;
+        }
+        let (x, y) = (idx % grid.w, idx / grid.w);
+
+        for (nx, ny) in grid.open_neighbors(x, y) {
+            let v = ny * grid.w + nx;
+            let nd = cost.saturating_add(grid.step_weight((nx, ny), (x, y)));
+            if nd < dist[v] {
+                dist[v] = nd;
+                next[v] = Some(idx);
+                heap.push(State { cost: nd, idx: v });
+            }
+        }
+    }
+
+    let dist = dist
+        .into_iter()
+        .map(|d| if d == u64::MAX { None } else { Some(d) })
+        .collect();
+    Ok((dist, next))
+}
+
+// Dijkstra depuis (0,0); s'arrête dès que `goal` est fixé si fourni
+fn dijkstra(
+    grid: &Grid,
+    goal: Option<usize>,
+    mut prof: Option<&mut Profile>,
+    mut observe: Option<&mut dyn FnMut(usize, (usize, usize))>,
+) -> Result<(Vec<u64>, Vec<Option<usize>>), SolveError> {
+    let n = grid.w * grid.h;
+    let start = 0usize;
+    let mut watchdog = Watchdog::new(grid);
+
+    let mut dist = vec![u64::MAX; n];
+    let mut prev: Vec<Option<usize>> = vec![None; n];
+    let mut heap = BinaryHeap::new();
+    let mut settled = 0usize;
+
+    dist[start] = 0;
+    heap.push(State {
+        cost: 0,
+        idx: start,
+    });
+
+    while let Some(State { cost, idx }) = heap.pop() {
+        watchdog.tick()?;
+        if let Some(p) = prof.as_deref_mut() {
+            p.heap_pops += 1;
+        }
+        if cost != dist[idx] {
+            continue;
+        }
+        let x = idx % grid.w;
+        let y = idx / grid.w;
+        settled += 1;
+        if let Some(f) = observe.as_deref_mut() {
+            f(settled, (x, y));
+        }
+        if Some(idx) == goal {
+            break;
+        }
+
+        for (nx, ny) in grid.open_neighbors(x, y) {
+            let nidx = ny * grid.w + nx;
+            let w = grid.step_weight((x, y), (nx, ny));
+            let next = cost.saturating_add(w);
+            if next < dist[nidx] {
+                if let Some(p) = prof.as_deref_mut() {
+                    p.relaxations += 1;
+                }
+                dist[nidx] = next;
+                prev[nidx] = Some(idx);
+                heap.push(State {
+                    cost: next,
+                    idx: nidx,
+                });
+            }
+        }
+    }
+
+    Ok((dist, prev))
+}
+
+/// Result of `multi_goal_costs`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GoalCosts {
+    /// Min cost to each goal, in the order given; `None` when unreachable.
+    pub costs: Vec<Option<u64>>,
+    /// Index of the cheapest reachable goal (first listed on ties) and the path to it.
+    pub best: Option<(usize, Vec<(usize, usize)>)>,
+}
+
+/// Min cost from the start to each of `goals` from a single Dijkstra sweep.
+pub fn multi_goal_costs(grid: &Grid, goals: &[(usize, usize)]) -> Result<GoalCosts, String> {
+    let idxs = goals
+        .iter()
+        .map(|&(x, y)| {
+            grid.idx(x, y)
+                .ok_or_else(|| format!("goal ({x},{y}) is outside the {}x{} grid", grid.w, grid.h))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Pas d'arrêt anticipé: un seul balayage donne la distance de toutes les cases
+    let (dist, prev) = dijkstra(grid, None, None, None).map_err(|e| e.to_string())?;
+    let costs: Vec<Option<u64>> = idxs
+        .iter()
+        .map(|&i| (dist[i] != u64::MAX).then_some(dist[i]))
+        .collect();
+
+    let best = costs
+        .iter()
+        .enumerate()
+        .filter_map(|(n, c)| c.map(|c| (c, n)))
+        .min()
+        .map(|(_, n)| (n, reconstruct_path(prev, grid.w, idxs[n])));
+    Ok(GoalCosts { costs, best })
+}
+
+/// Result of `farthest_cell`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Farthest {
+    pub cell: (usize, usize),
+    /// Min cost from the start to `cell`.
+    pub cost: u64,
+    pub path: Vec<(usize, usize)>,
+}
+
+/// The reachable cell with the largest min cost from the start, and the path
+/// to it: the map's hardest cell to reach.
+///
+/// Ties go to the first cell in row-major order (smallest `y`, then smallest `x`).
+/// On a 1x1 grid the start itself is returned with cost 0.
+pub fn farthest_cell(grid: &Grid) -> Result<Farthest, SolveError> {
+    let (dist, prev) = dijkstra(grid, None, None, None)?;
+    let mut far = 0;
+    for (i, &d) in dist.iter().enumerate() {
+        if d != u64::MAX && d > dist[far] {
+            far = i;
+        }
+    }
+    Ok(Farthest {
+        cell: (far % grid.w, far / grid.w),
+        cost: dist[far],
+        path: reconstruct_path(prev, grid.w, far),
+    })
+}
+
+/*MAX COST parmi les chemins à nombre de pas minimal*/
+
+/// Maximum total cost among the paths with the minimal number of steps.
+pub fn max_cost_among_shortest_paths(grid: &Grid) -> Option<(u64, Vec<(usize, usize)>)> {
+    max_cost_impl(grid, None).ok()
+}
+
+/// `max_cost_among_shortest_paths`, recording the BFS and DP phase times in `prof`.
+pub fn max_cost_among_shortest_paths_profiled(
+    grid: &Grid,
+    prof: &mut Profile,
+) -> Option<(u64, Vec<(usize, usize)>)> {
+    max_cost_impl(grid, Some(prof)).ok()
+}
+
+/// `max_cost_among_shortest_paths` (profiled when `prof` is given) that tells
+/// an unreachable goal apart from a solve cut short by `SolveOptions::time_limit`.
+pub fn try_max_cost_among_shortest_paths(
+    grid: &Grid,
+    prof: Option<&mut Profile>,
+) -> Result<(u64, Vec<(usize, usize)>), SolveError> {
+    max_cost_impl(grid, prof)
+}
+
+fn max_cost_impl(
+    grid: &Grid,
+    mut prof: Option<&mut Profile>,
+) -> Result<(u64, Vec<(usize, usize)>), SolveError> {
+    let n = grid.w * grid.h;
+    let start = 0usize;
+    let goal = n - 1;
+    if goal == start {
+        return Ok(trivial_path());
+    }
+    let mut watchdog = Watchdog::new(grid);
+
+    // BFS pour distance en nombre de pas
+    let mut phase = prof.is_some().then(Instant::now);
+    let mut step = vec![i32::MAX; n];
+    let mut q = VecDeque::new();
+    step[start] = 0;
+    q.push_back(start);
+
+    while let Some(idx) = q.pop_front() {
+        watchdog.tick()?;
+        let x = idx % grid.w;
+        let y = idx / grid.w;
+        let d = step[idx];
+
+        for (nx, ny) in grid.open_neighbors(x, y) {
+            let nidx = ny * grid.w + nx;
+            if step[nidx] == i32::MAX {
+                step[nidx] = d + 1;
+                q.push_back(nidx);
+            }
+        }
+    }
+
+    if let (Some(p), Some(t)) = (prof.as_deref_mut(), phase) {
+        p.bfs = t.elapsed();
+        phase = Some(Instant::now());
+    }
+
+    let goal_d = step[goal];
+    if goal_d == i32::MAX {
+        return Err(SolveError::NoPath);
+    }
+
+    // DP pour coût max sur le DAG des distances
+    let mut best = vec![i64::MIN; n];
+    let mut prev: Vec<Option<usize>> = vec![None; n];
+    best[start] = 0;
+
+    let mut layers: Vec<Vec<usize>> = vec![Vec::new(); (goal_d as usize) + 1];
+    // Avec des cases infranchissables, certaines cellules sont plus loin que le but
+    for (i, &d) in step.iter().enumerate() {
+        if d <= goal_d {
+            layers[d as usize].push(i);
+        }
+    }
+
+    let limit = goal_d as usize;
+    for (d, layer) in layers.iter().enumerate().take(limit) {
+        for &idx in layer {
+            watchdog.tick()?;
+            if best[idx] == i64::MIN {
+                continue;
+            }
+            let x = idx % grid.w;
+            let y = idx / grid.w;
+            for (nx, ny) in grid.open_neighbors(x, y) {
+                let nidx = ny * grid.w + nx;
+                if step[nidx] == (d as i32) + 1 {
+                    let add = grid.step_weight((x, y), (nx, ny)).min(i64::MAX as u64) as i64;
+                    let cand = best[idx].saturating_add(add);
+                    if cand > best[nidx] {
+                        best[nidx] = cand;
+                        prev[nidx] = Some(idx);
+                    }
+                }
+            }
+        }
+    }
+
+    if let (Some(p), Some(t)) = (prof, phase) {
+        p.dp = t.elapsed();
+    }
+
+    if best[goal] == i64::MIN {
+        return Err(SolveError::NoPath);
+    }
+
+    let path = reconstruct_path(prev, grid.w, goal);
+    Ok((best[goal] as u64, path))
+}
+
+/*WIDEST PATH (maximin)*/
+
+/// Path whose lowest entered cell is as high as possible (maximum-capacity
+/// path), returned with that clearance. The start is not counted, like in
+/// costs; among equally wide paths the one with the fewest steps wins.
+pub fn widest_path(grid: &Grid) -> Result<(u8, Vec<(usize, usize)>), SolveError> {
+    let n = grid.w * grid.h;
+    let goal = n - 1;
+    let mut watchdog = Watchdog::new(grid);
+
+    // (clearance, pas) comparés lexicographiquement: plus large, puis plus court
+    let mut best: Vec<Option<(u8, usize)>> = vec![None; n];
+    let mut prev: Vec<Option<usize>> = vec![None; n];
+    let mut heap = BinaryHeap::new();
+
+    best[0] = Some((0xFF, 0));
+    heap.push((0xFFu8, Reverse(0usize), 0usize));
+
+    while let Some((width, Reverse(steps), idx)) = heap.pop() {
+        watchdog.tick()?;
+        if best[idx] != Some((width, steps)) {
+            continue;
+        }
+        if idx == goal {
+            break;
+        }
+
+        let (x, y) = (idx % grid.w, idx / grid.w);
+        for (nx, ny) in grid.open_neighbors(x, y) {
+            let v = ny * grid.w + nx;
+            let cand = (width.min(grid.at(nx, ny).unwrap_or(0)), steps + 1);
+            let better = match best[v] {
+                None => true,
+                Some((bw, bs)) => cand.0 > bw || (cand.0 == bw && cand.1 < bs),
+            };
+            if better {
+                best[v] = Some(cand);
+                prev[v] = Some(idx);
+                heap.push((cand.0, Reverse(cand.1), v));
+            }
+        }
+    }
+
+    let Some((width, _)) = best[goal] else {
+        return Err(SolveError::NoPath);
+    };
+    Ok((width, reconstruct_path(prev, grid.w, goal)))
+}
+
+/*PATH CHECK*/
+
+/// Parses a path file: one `x,y` coordinate per line, blank lines ignored.
+pub fn parse_path_text(content: &str) -> Result<Vec<(usize, usize)>, String> {
+    let mut path = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (xs, ys) = line
+            .split_once(',')
+            .ok_or_else(|| format!("line {}: expected x,y, got '{line}'", i + 1))?;
+        let x = xs
+            .trim()
+            .parse()
+            .map_err(|_| format!("line {}: invalid x in '{line}'", i + 1))?;
+        let y = ys
+            .trim()
+            .parse()
+            .map_err(|_| format!("line {}: invalid y in '{line}'", i + 1))?;
+        path.push((x, y));
+    }
+    Ok(path)
+}
+
+/// Sum of the values of every cell entered after the start, each scaled by
+/// the multiplier of the move that entered it.
+pub fn path_cost(grid: &Grid, path: &[(usize, usize)]) -> u64 {
+    path.windows(2)
+        .map(|pair| grid.step_weight(pair[0], pair[1]))
+        .fold(0u64, |acc, v| acc.saturating_add(v))
+}
+
+/// Checks that `path` walks legal 4-neighbour moves from the start to the goal
+/// and returns its cost, or a message describing the first illegal step.
+pub fn check_path(grid: &Grid, path: &[(usize, usize)]) -> Result<u64, String> {
+    let Some(&first) = path.first() else {
+        return Err("path is empty".to_string());
+    };
+    if first != (0, 0) {
+        return Err(format!("path starts at {first:?}, expected (0, 0)"));
+    }
+    for (i, &(x, y)) in path.iter().enumerate() {
+        if grid.idx(x, y).is_none() {
+            return Err(format!("step {i}: ({x},{y}) is outside the grid"));
+        }
+        if !grid.passable(x, y) {
+            return Err(format!("step {i}: ({x},{y}) is above the --max-cell limit"));
+        }
+        if i > 0 {
+            let (px, py) = path[i - 1];
+            if px.abs_diff(x) + py.abs_diff(y) != 1 {
+                return Err(format!(
+                    "step {i}: ({px},{py}) -> ({x},{y}) is not a move to an adjacent cell"
+                ));
+            }
+        }
+    }
+    let goal = (grid.w - 1, grid.h - 1);
+    let last = path[path.len() - 1];
+    if last != goal {
+        return Err(format!(
+            "path ends at ({},{}), expected ({},{})",
+            last.0, last.1, goal.0, goal.1
+        ));
+    }
+    Ok(path_cost(grid, path))
+}
+
+/// Fails on the first cell visited twice (paths from the solvers must be simple).
+pub fn check_simple_path(path: &[(usize, usize)]) -> Result<(), String> {
+    let mut seen = std::collections::HashSet::with_capacity(path.len());
+    for (i, &(x, y)) in path.iter().enumerate() {
+        if !seen.insert((x, y)) {
+            return Err(format!("cell ({x},{y}) revisited at step {i}"));
+        }
+    }
+    Ok(())
+}
+
+/*BASELINE GLOUTONNE*/
+
+/// Naive greedy walk from the start to the goal: always steps to the cheapest
+/// unvisited neighbour (ties go to the one closest to the goal) and backtracks
+/// out of dead ends. Returns the cost and the cells of the final walk.
+pub fn greedy_walk(grid: &Grid) -> Result<(u64, Vec<(usize, usize)>), String> {
+    let goal = (grid.w - 1, grid.h - 1);
+    let mut visited = vec![false; grid.w * grid.h];
+    visited[0] = true;
+    let mut path = vec![(0, 0)];
+
+    while let Some(&(x, y)) = path.last() {
+        if (x, y) == goal {
+            return Ok((path_cost(grid, &path), path));
+        }
+        let next = grid
+            .open_neighbors(x, y)
+            .into_iter()
+            .filter(|&(nx, ny)| grid.idx(nx, ny).is_some_and(|i| !visited[i]))
+            .min_by_key(|&(nx, ny)| {
+                let to_goal = nx.abs_diff(goal.0) + ny.abs_diff(goal.1);
+                (grid.step_weight((x, y), (nx, ny)), to_goal)
+            });
+        match next {
+            Some((nx, ny)) => {
+                if let Some(i) = grid.idx(nx, ny) {
+                    visited[i] = true;
+                }
+                path.push((nx, ny));
+            }
+            // Cul-de-sac: on recule d'une case
+            None => {
+                path.pop();
+            }
+        }
+    }
+    Err("greedy walk found no path".to_string())
+}
+
+/*MIN COST puis MIN VIRAGES*/
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+struct TurnState {
+    cost: u64,
+    turns: usize,
+    node: usize,
+}
+
+impl Ord for TurnState {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.cost, other.turns, other.node).cmp(&(self.cost, self.turns, self.node))
+    }
+}
+
+impl PartialOrd for TurnState {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Among minimum-cost paths, the one with the fewest direction changes
+/// (see `count_turns`).
+pub fn min_cost_min_turns(grid: &Grid) -> Result<(u64, Vec<(usize, usize)>), SolveError> {
+    min_turns_impl(grid, None)
+}
+
+/// `min_cost_min_turns`, recording heap pops, relaxations and solve time in `prof`.
+pub fn min_cost_min_turns_profiled(
+    grid: &Grid,
+    prof: &mut Profile,
+) -> Result<(u64, Vec<(usize, usize)>), SolveError> {
+    let started = Instant::now();
+    let res = min_turns_impl(grid, Some(&mut *prof));
+    prof.min_solve = started.elapsed();
+    res
+}
+
+fn min_turns_impl(
+    grid: &Grid,
+    mut prof: Option<&mut Profile>,
+) -> Result<(u64, Vec<(usize, usize)>), SolveError> {
+    // Nœud = cellule * 5 + direction d'arrivée (4 = aucune, pour le départ)
+    const NO_DIR: usize = 4;
+    let n = grid.w * grid.h;
+    let goal = n - 1;
+    if goal == 0 {
+        return Ok(trivial_path());
+    }
+    let start = NO_DIR;
+
+    let mut best = vec![(u64::MAX, usize::MAX); n * 5];
+    let mut prev: Vec<Option<usize>> = vec![None; n * 5];
+    let mut heap = BinaryHeap::new();
+
+    best[start] = (0, 0);
+    heap.push(TurnState {
+        cost: 0,
+        turns: 0,
+        node: start,
+    });
+
+    let mut watchdog = Watchdog::new(grid);
+    let mut reached = None;
+    while let Some(TurnState { cost, turns, node }) = heap.pop() {
+        watchdog.tick()?;
+        if let Some(p) = prof.as_deref_mut() {
+            p.heap_pops += 1;
+        }
+        if (cost, turns) != best[node] {
+            continue;
+        }
+        let idx = node / 5;
+        let dir = node % 5;
+        if idx == goal {
+            reached = Some(node);
+            break;
+        }
+
+        let here = (idx % grid.w, idx / grid.w);
+        for (nx, ny) in grid.open_neighbors(here.0, here.1) {
+            let ndir = step_direction(here, (nx, ny)) as usize;
+            let next = (
+                cost.saturating_add(grid.step_weight(here, (nx, ny))),
+                turns + usize::from(dir != NO_DIR && dir != ndir),
+            );
+            let nnode = (ny * grid.w + nx) * 5 + ndir;
+            if next < best[nnode] {
+                if let Some(p) = prof.as_deref_mut() {
+                    p.relaxations += 1;
+                }
+                best[nnode] = next;
+                prev[nnode] = Some(node);
+                heap.push(TurnState {
+                    cost: next.0,
+                    turns: next.1,
+                    node: nnode,
+                });
+            }
+        }
+    }
+
+    let end = reached.ok_or(SolveError::NoPath)?;
+    let mut path = Vec::new();
+    let mut cur = Some(end);
+    while let Some(node) = cur {
+        let idx = node / 5;
+        path.push((idx % grid.w, idx / grid.w));
+        cur = prev[node];
+    }
+    path.reverse();
+
+    Ok((best[end].0, path))
+}
+
+/*DIRECTIONS*/
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Direction::Up => "Up",
+            Direction::Down => "Down",
+            Direction::Left => "Left",
+            Direction::Right => "Right",
+        };
+        f.write_str(name)
+    }
+}
+
+fn step_direction((x0, y0): (usize, usize), (x1, y1): (usize, usize)) -> Direction {
+    if x1 > x0 {
+        Direction::Right
+    } else if x1 < x0 {
+        Direction::Left
+    } else if y1 > y0 {
+        Direction::Down
+    } else {
+        Direction::Up
+    }
+}
+
+/// Run-length encoded moves along a path of adjacent cells.
+pub fn path_directions(path: &[(usize, usize)]) -> Vec<(Direction, usize)> {
+    let mut out: Vec<(Direction, usize)> = Vec::new();
+    for pair in path.windows(2) {
+        let dir = step_direction(pair[0], pair[1]);
+        match out.last_mut() {
+            Some((last, n)) if *last == dir => *n += 1,
+            _ => out.push((dir, 1)),
+        }
+    }
+    out
+}
+
+/// Number of direction changes along a path.
+pub fn count_turns(path: &[(usize, usize)]) -> usize {
+    path_directions(path).len().saturating_sub(1)
+}
+
+/*APERÇU BRAILLE*/
+
+// Bit de chaque point d'un caractère Braille, indexé par [ligne][colonne] du bloc 2x4
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// Smallest `scale` (cells per dot side) that fits the grid in `max_cols` glyphs.
+pub fn preview_scale(grid: &Grid, max_cols: usize) -> usize {
+    grid.w.div_ceil(2 * max_cols.max(1)).max(1)
+}
+
+/// Downscaled rendering with one Braille glyph per 2x4 dots. Each dot stands for
+/// a `scale` x `scale` block of cells and is set when the block's average value
+/// exceeds `threshold`; blocks crossed by `path` are always set.
+pub fn braille_preview(
+    grid: &Grid,
+    scale: usize,
+    threshold: u8,
+    path: Option<&[(usize, usize)]>,
+) -> Vec<String> {
+    let scale = scale.max(1);
+    let dots_w = grid.w.div_ceil(scale);
+    let dots_h = grid.h.div_ceil(scale);
+
+    // Somme et nombre de cellules par bloc (les blocs du bord peuvent être partiels)
+    let mut sums = vec![0u64; dots_w * dots_h];
+    let mut counts = vec![0u64; dots_w * dots_h];
+    for y in 0..grid.h {
+        for x in 0..grid.w {
+            let d = (y / scale) * dots_w + x / scale;
+            sums[d] += grid.cells[y * grid.w + x] as u64;
+            counts[d] += 1;
+        }
+    }
+    let mut set: Vec<bool> = sums
+        .iter()
+        .zip(&counts)
+        .map(|(&sum, &n)| sum > threshold as u64 * n)
+        .collect();
+    for &(x, y) in path.unwrap_or(&[]) {
+        if x < grid.w && y < grid.h {
+            set[(y / scale) * dots_w + x / scale] = true;
+        }
+    }
+
+    (0..dots_h.div_ceil(4))
+        .map(|gy| {
+            (0..dots_w.div_ceil(2))
+                .map(|gx| {
+                    let mut bits = 0u32;
+                    for (row, cols) in BRAILLE_DOTS.iter().enumerate() {
+                        for (col, &bit) in cols.iter().enumerate() {
+                            let (dx, dy) = (gx * 2 + col, gy * 4 + row);
+                            if dx < dots_w && dy < dots_h && set[dy * dots_w + dx] {
+                                bits |= bit;
+                            }
+                        }
+                    }
+                    char::from_u32(0x2800 + bits).unwrap_or(' ')
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/*IMAGE DES DISTANCES*/
+
+// Rouge sombre -> rouge -> jaune -> blanc pour t dans [0, 1]; le noir reste aux cases inaccessibles
+fn heat_rgb(t: f64) -> [u8; 3] {
+    let t = t.clamp(0.0, 1.0) * 3.0;
+    let ramp = |x: f64| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [
+        64 + (t.min(1.0) * 191.0).round() as u8,
+        ramp(t - 1.0),
+        ramp(t - 2.0),
+    ]
+}
+
+/// Binary PPM (P6) with one pixel per cell, colored by `dist` (from `distance_map`)
+/// normalized so the farthest reachable cell is white; unreachable cells are black.
+pub fn distance_heat_ppm(grid: &Grid, dist: &[Option<u64>]) -> Vec<u8> {
+    let max = dist.iter().flatten().copied().max().unwrap_or(0);
+    let mut out = format!("P6\n{} {}\n255\n", grid.w, grid.h).into_bytes();
+    out.reserve(grid.w * grid.h * 3);
+    for d in dist.iter().take(grid.w * grid.h) {
+        let rgb = match *d {
+            None => [0, 0, 0],
+            // Carte réduite au départ: seule case atteinte, donc la plus lointaine
+            Some(_) if max == 0 => [255, 255, 255],
+            Some(d) => heat_rgb(d as f64 / max as f64),
+        };
+        out.extend_from_slice(&rgb);
+    }
+    out
+}
+
+/*util*/
+
+/// In-bounds 4-neighbours of `(x, y)`: up, down, left, right.
+pub fn neighbors4(x: usize, y: usize, w: usize, h: usize) -> Vec<(usize, usize)> {
+    let mut out = Vec::with_capacity(4);
+    if y > 0 {
+        out.push((x, y - 1));
+    }
+    if y + 1 < h {
+        out.push((x, y + 1));
+    }
+    if x > 0 {
+        out.push((x - 1, y));
+    }
+    if x + 1 < w {
+        out.push((x + 1, y));
+    }
+    out
+}
+
+fn reconstruct_path(prev: Vec<Option<usize>>, w: usize, goal: usize) -> Vec<(usize, usize)> {
+    let mut out = Vec::new();
+    let mut cur = Some(goal);
+    while let Some(i) = cur {
+        out.push((i % w, i / w));
+        cur = prev[i];
+    }
+    out.reverse();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(text: &str) -> Grid {
+        parse_grid_text(text).expect("valid test grid")
+    }
+
+    #[test]
+    fn two_by_two() {
+        let g = grid("00 01\n05 FF\n");
+        let (cost, path) = dijkstra_min_cost(&g).unwrap();
+        assert_eq!(cost, 0x01 + 0xFF);
+        assert_eq!(path, vec![(0, 0), (1, 0), (1, 1)]);
+
+        let (max, max_path) = max_cost_among_shortest_paths(&g).unwrap();
+        assert_eq!(max, 0x05 + 0xFF);
+        assert_eq!(max_path, vec![(0, 0), (0, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn corridor() {
+        let g = grid("00 10 20 30 FF");
+        let (cost, path) = dijkstra_min_cost(&g).unwrap();
+        assert_eq!(cost, 0x10 + 0x20 + 0x30 + 0xFF);
+        assert_eq!(path.len(), 5);
+
+        let (max, _) = max_cost_among_shortest_paths(&g).unwrap();
+        assert_eq!(max, cost);
+    }
+
+    #[test]
+    fn cheaper_detour() {
+        // La ligne du milieu est chère : le chemin min fait le tour par le haut
+        let g = grid(
+            "00 01 01 01\n\
+             F0 F0 F0 01\n\
+             01 01 01 FF\n",
+        );
+        let (cost, path) = dijkstra_min_cost(&g).unwrap();
+        assert_eq!(cost, 3 + 1 + 0xFF);
+        assert_eq!(path, vec![(0, 0), (1, 0), (2, 0), (3, 0), (3, 1), (3, 2)]);
+        assert_eq!(path_cost(&g, &path), cost);
+
+        let (max, max_path) = max_cost_among_shortest_paths(&g).unwrap();
+        assert_eq!(max_path.len(), path.len());
+        assert_eq!(path_cost(&g, &max_path), max);
+        assert!(max > cost);
+    }
+
+    #[test]
+    fn distance_map_matches_min_cost() {
+        let g = grid("00 01 02\n03 04 05\n06 07 FF\n");
+        let dist = distance_map(&g).unwrap();
+        assert_eq!(dist[0], Some(0));
+        assert_eq!(dist[1], Some(1));
+        assert_eq!(dist[3], Some(3));
+        assert_eq!(dist[4], Some(5));
+        let (cost, _) = dijkstra_min_cost(&g).unwrap();
+        assert_eq!(dist[8], Some(cost));
+    }
+
+    #[test]
+    fn directions_collapse_runs() {
+        let path = vec![(0, 0), (1, 0), (2, 0), (2, 1), (2, 2), (3, 2)];
+        assert_eq!(
+            path_directions(&path),
+            vec![
+                (Direction::Right, 2),
+                (Direction::Down, 2),
+                (Direction::Right, 1)
+            ]
+        );
+        assert!(path_directions(&[(0, 0)]).is_empty());
+    }
+
+    #[test]
+    fn generation_is_seeded_and_keeps_corners() {
+        use rand::SeedableRng;
+        use rand::rngs::StdRng;
+
+        for dist in [
+            Distribution::Uniform,
+            Distribution::Gradient,
+            Distribution::Clustered,
+        ] {
+            let a = generate_grid_with(12, 7, dist, &mut StdRng::seed_from_u64(7));
+            let b = generate_grid_with(12, 7, dist, &mut StdRng::seed_from_u64(7));
+            assert_eq!(a.cells, b.cells);
+            assert!(validate_grid(&a).is_ok());
+        }
+
+        let g = generate_grid_with(
+            16,
+            16,
+            Distribution::Gradient,
+            &mut StdRng::seed_from_u64(1),
+        );
+        let sum = |x0: usize, y0: usize| -> u64 {
+            (0..4)
+                .flat_map(|dy| (0..4).map(move |dx| (x0 + dx, y0 + dy)))
+                .map(|(x, y)| g.at(x, y).unwrap() as u64)
+                .sum()
+        };
+        assert!(sum(0, 0) < sum(12, 12));
+    }
+
+    #[test]
+    fn check_path_accepts_and_rejects() {
+        let g = grid("00 01\n05 FF\n");
+        let ok = parse_path_text("0,0\n1,0\n\n1,1\n").unwrap();
+        assert_eq!(check_path(&g, &ok), Ok(0x01 + 0xFF));
+
+        let jump = vec![(0, 0), (1, 1)];
+        assert!(check_path(&g, &jump).unwrap_err().starts_with("step 1"));
+        let short = vec![(0, 0), (1, 0)];
+        assert!(check_path(&g, &short).is_err());
+        assert!(parse_path_text("0;0").is_err());
+    }
+
+    #[test]
+    fn min_turns_breaks_ties_without_changing_cost() {
+        let g = grid("00 01 01 01\n01 01 01 01\n01 01 01 01\n01 01 01 FF\n");
+        let (cost, _) = dijkstra_min_cost(&g).unwrap();
+        let (tcost, path) = min_cost_min_turns(&g).unwrap();
+        assert_eq!(tcost, cost);
+        assert_eq!(path_cost(&g, &path), cost);
+        assert_eq!(count_turns(&path), 1);
+    }
+
+    #[test]
+    fn bands_reweight_cells() {
+        let band: Band = "80:FF:3".parse().unwrap();
+        assert_eq!(
+            band,
+            Band {
+                lo: 0x80,
+                hi: 0xFF,
+                mult: 3
+            }
+        );
+        assert!("90:10:2".parse::<Band>().is_err());
+        assert!("10:20".parse::<Band>().is_err());
+
+        let first: Band = "00:0F:10".parse().unwrap();
+        assert_eq!(
+            effective_cost(0x05, &[first, "00:FF:2".parse().unwrap()]),
+            50
+        );
+        assert_eq!(effective_cost(0x10, &[first]), 0x10);
+
+        // Sans bande le chemin du haut est le moins cher; x10 sur 01-0F le rend plus cher
+        let mut g = grid("00 01 01\n20 20 01\n20 20 FF\n");
+        let (_, plain) = dijkstra_min_cost(&g).unwrap();
+        assert_eq!(plain[1], (1, 0));
+        g.opts.bands = vec!["01:0F:100".parse().unwrap()];
+        let (cost, banded) = dijkstra_min_cost(&g).unwrap();
+        assert_eq!(banded[1], (0, 1));
+        assert_eq!(path_cost(&g, &banded), cost);
+    }
+
+    #[test]
+    fn reader_matches_text_parser() {
+        let inputs = [
+            "00 01\n05 FF\n",
+            "\n  00, 0x10; 20\n\n30 40 FF  \n",
+            "00 10 20 30 FF",
+            "",
+            "00 01\n02\n",
+            "00 GG",
+            "00 123",
+        ];
+        for input in inputs {
+            let text = parse_grid_text(input);
+            let streamed = parse_grid_reader(input.as_bytes());
+            match (text, streamed) {
+                (Ok(a), Ok(b)) => {
+                    assert_eq!((a.w, a.h, a.cells), (b.w, b.h, b.cells), "{input:?}");
+                }
+                (Err(_), Err(_)) => {}
+                (a, b) => panic!("parsers disagree on {input:?}: {a:?} vs {b:?}"),
+            }
+        }
+
+        let err = parse_grid_reader("00 01\n02 03\n04\n".as_bytes()).unwrap_err();
+        assert_eq!(err, "non-rectangular map at row 2");
+        let err = parse_grid_reader("00 01\n02 ZZ\n".as_bytes()).unwrap_err();
+        assert!(err.starts_with("row 1:"), "{err}");
+    }
+
+    #[test]
+    fn solver_paths_are_simple() {
+        let g = grid("00 01 01 01\nF0 F0 F0 01\n01 01 01 FF\n");
+        let (_, min_path) = dijkstra_min_cost(&g).unwrap();
+        let (_, max_path) = max_cost_among_shortest_paths(&g).unwrap();
+        assert!(check_simple_path(&min_path).is_ok());
+        assert!(check_simple_path(&max_path).is_ok());
+
+        let looped = vec![(0, 0), (1, 0), (1, 1), (0, 1), (0, 0), (1, 0)];
+        assert_eq!(
+            check_simple_path(&looped),
+            Err("cell (0,0) revisited at step 4".to_string())
+        );
+    }
+
+    #[test]
+    fn greedy_walk_reaches_goal_and_never_beats_optimum() {
+        // Le glouton évite FF, s'enferme en (1,0) puis recule jusqu'en (2,1)
+        let g = grid("00 F0 F0\n01 01 F0\n01 01 FF\n");
+        let (cost, path) = greedy_walk(&g).unwrap();
+        assert_eq!(
+            path,
+            vec![(0, 0), (0, 1), (0, 2), (1, 2), (1, 1), (2, 1), (2, 2)]
+        );
+        assert_eq!(cost, 4 + 0xF0 + 0xFF);
+        assert!(check_path(&g, &path).is_ok());
+        assert!(check_simple_path(&path).is_ok());
+        assert_eq!(path_cost(&g, &path), cost);
+
+        let (optimal, _) = dijkstra_min_cost(&g).unwrap();
+        assert_eq!(optimal, 3 + 0xFF);
+    }
+
+    #[test]
+    fn comments_and_separators_are_ignored() {
+        let annotated = "# petite map de test\n00 01 # départ\n---\n\n  # rien ici\n05 FF # goal\n";
+        for g in [
+            parse_grid_text(annotated).unwrap(),
+            parse_grid_reader(annotated.as_bytes()).unwrap(),
+        ] {
+            assert_eq!((g.w, g.h), (2, 2));
+            assert_eq!(g.cells, vec![0x00, 0x01, 0x05, 0xFF]);
+        }
+
+        assert!(parse_grid_text("00 01 # ok\n05 GG # pas ok\n").is_err());
+        assert!(parse_grid_text("00 --- 01\n").is_err());
+    }
+
+    #[test]
+    fn profiled_solvers_match_and_count() {
+        let g = grid("00 01 01 01\nF0 F0 F0 01\n01 01 01 FF\n");
+        let mut prof = Profile::default();
+        assert_eq!(
+            dijkstra_min_cost_profiled(&g, &mut prof).unwrap(),
+            dijkstra_min_cost(&g).unwrap()
+        );
+        assert!(prof.heap_pops > 0);
+        assert!(prof.relaxations >= prof.heap_pops - 1);
+        assert_eq!(
+            max_cost_among_shortest_paths_profiled(&g, &mut prof),
+            max_cost_among_shortest_paths(&g)
+        );
+
+        let mut turns = Profile::default();
+        assert_eq!(
+            min_cost_min_turns_profiled(&g, &mut turns).unwrap(),
+            min_cost_min_turns(&g).unwrap()
+        );
+        assert!(turns.heap_pops > 0);
+    }
+
+    #[test]
+    fn binary_round_trip_and_errors() {
+        let g = grid("00 01 02\n10 11 FF\n");
+        let mut bytes = Vec::new();
+        write_grid_bin(&mut bytes, &g).unwrap();
+        assert_eq!(&bytes[..4], b"HXPG");
+        assert_eq!(bytes.len(), 12 + 6);
+
+        let back = read_grid_bin(bytes.as_slice()).unwrap();
+        assert_eq!((back.w, back.h, back.cells), (g.w, g.h, g.cells));
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert!(read_grid_bin(bad_magic.as_slice()).is_err());
+        assert!(read_grid_bin(&bytes[..bytes.len() - 1]).is_err());
+        assert!(read_grid_bin(&bytes[..8]).is_err());
+
+        let mut huge = bytes[..4].to_vec();
+        huge.extend_from_slice(&(MAX_SIDE as u32 + 1).to_be_bytes());
+        huge.extend_from_slice(&1u32.to_be_bytes());
+        assert_eq!(
+            read_grid_bin(huge.as_slice()).unwrap_err(),
+            "grid too large"
+        );
+    }
+
+    #[test]
+    fn max_cell_detour_past_the_goal_depth() {
+        // (1,2) et (0,2) ne s'atteignent qu'en passant par l'arrivée: plus loin que le but
+        let mut g = grid("00 01 01\nF0 F0 01\n01 01 FF\n");
+        g.opts.max_cell = Some(0x10);
+        let (cost, path) = max_cost_among_shortest_paths(&g).unwrap();
+        assert_eq!(path, vec![(0, 0), (1, 0), (2, 0), (2, 1), (2, 2)]);
+        assert_eq!(cost, 1 + 1 + 1 + 0xFF);
+    }
+
+    #[test]
+    fn max_cell_blocks_the_direct_route() {
+        // Sans limite, le plus court passe par 90; avec 0x80 il faut contourner par 40/60
+        let mut g = grid("00 90 01\n40 60 01\n40 40 FF\n");
+        let (cost, direct) = dijkstra_min_cost(&g).unwrap();
+        assert_eq!(cost, 0x90 + 1 + 1 + 0xFF);
+        assert!(direct.contains(&(1, 0)));
+
+        g.opts.max_cell = Some(0x80);
+        assert_eq!(g.excluded_cells(), 1);
+        let (cost, path) = dijkstra_min_cost(&g).unwrap();
+        assert_eq!(path, vec![(0, 0), (0, 1), (1, 1), (2, 1), (2, 2)]);
+        assert_eq!(cost, 0x40 + 0x60 + 1 + 0xFF);
+        let (_, max_path) = max_cost_among_shortest_paths(&g).unwrap();
+        assert!(max_path.iter().all(|&(x, y)| g.passable(x, y)));
+        assert!(check_path(&g, &direct).is_err());
+
+        // Mur complet: plus aucun chemin
+        let mut walled = grid("00 F0 01\nF0 01 01\n01 01 FF\n");
+        walled.opts.max_cell = Some(0x10);
+        assert!(dijkstra_min_cost(&walled).is_err());
+        assert!(max_cost_among_shortest_paths(&walled).is_none());
+        assert!(min_cost_min_turns(&walled).is_err());
+    }
+
+    #[test]
+    fn avoided_cells_force_a_detour() {
+        let mut g = grid("00 01 01\n50 60 01\n50 50 FF\n");
+        let (cost, path) = dijkstra_min_cost(&g).unwrap();
+        assert_eq!(path, vec![(0, 0), (1, 0), (2, 0), (2, 1), (2, 2)]);
+        assert_eq!(cost, 1 + 1 + 1 + 0xFF);
+
+        g.opts.avoid = HashSet::from([(2, 0)]);
+        assert_eq!(g.excluded_cells(), 0);
+        let (cost, path) = dijkstra_min_cost(&g).unwrap();
+        assert_eq!(path, vec![(0, 0), (1, 0), (1, 1), (2, 1), (2, 2)]);
+        assert_eq!(cost, 1 + 0x60 + 1 + 0xFF);
+        assert!(path.iter().all(|&(x, y)| g.passable(x, y)));
+
+        // Les deux voisins de l'arrivée interdits: plus de chemin
+        g.opts.avoid = HashSet::from([(2, 1), (1, 2)]);
+        assert!(dijkstra_min_cost(&g).is_err());
+        assert!(distance_map(&g).unwrap()[8].is_none());
+    }
+
+    #[test]
+    fn diff_grids_lists_changed_cells() {
+        let a = grid("00 01 02\n03 04 FF\n");
+        let b = grid("00 01 09\n07 04 FF\n");
+        assert_eq!(
+            diff_grids(&a, &b).unwrap(),
+            vec![(2, 0, 0x02, 0x09), (0, 1, 0x03, 0x07)]
+        );
+        assert!(diff_grids(&a, &a).unwrap().is_empty());
+        assert!(diff_grids(&a, &grid("00 FF")).is_err());
+    }
+
+    #[test]
+    fn carved_maps_stay_solvable_under_max_cell() {
+        use rand::SeedableRng;
+
+        for seed in 0..20 {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let mut g = generate_grid_with(12, 9, Distribution::Uniform, &mut rng);
+            let path = carve_monotone_path(&mut g, 0x20, &mut rng);
+
+            assert_eq!(path.len(), 12 + 9 - 1);
+            assert_eq!((g.cells[0], g.cells[g.cells.len() - 1]), (0x00, 0xFF));
+            g.opts.max_cell = Some(0x20);
+            assert!(path.iter().all(|&(x, y)| g.passable(x, y)));
+            assert!(dijkstra_min_cost(&g).is_ok(), "seed {seed}");
+            assert!(max_cost_among_shortest_paths(&g).is_some(), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn inversion_swaps_min_and_max() {
+        let g = grid("00 10 20\n30 40 50\n60 70 FF\n");
+        let inv = g.inverted();
+        assert_eq!(inv.cells[0], 0xFF);
+        assert_eq!(inv.cells[8], 0x00);
+        assert_eq!(inv.inverted().cells, g.cells);
+
+        // Même nombre de pas: coût inversé = 0xFF * pas - coût d'origine
+        let steps = (g.w + g.h - 2) as u64;
+        let (min, min_path) = dijkstra_min_cost(&g).unwrap();
+        let (max, max_path) = max_cost_among_shortest_paths(&g).unwrap();
+        let (inv_min, inv_min_path) = dijkstra_min_cost(&inv).unwrap();
+        let (inv_max, inv_max_path) = max_cost_among_shortest_paths(&inv).unwrap();
+        assert_eq!(inv_min, 0xFF * steps - max);
+        assert_eq!(inv_max, 0xFF * steps - min);
+        assert_eq!(inv_min_path, max_path);
+        assert_eq!(inv_max_path, min_path);
+    }
+
+    #[test]
+    fn goal_distances_follow_the_optimal_path() {
+        let g = grid(
+            "00 01 01 01\n\
+             F0 F0 F0 01\n\
+             01 01 01 FF\n",
+        );
+        let (min, path) = dijkstra_min_cost(&g).unwrap();
+        let from_start = distance_map(&g).unwrap();
+        let (to_goal, next) = goal_distance_map(&g).unwrap();
+        assert_eq!(to_goal[0], Some(min));
+        assert_eq!(to_goal[g.w * g.h - 1], Some(0));
+
+        // Sur le chemin optimal: départ + arrivée = coût min, et `next` suit le chemin
+        for pair in path.windows(2) {
+            let i = g.idx(pair[0].0, pair[0].1).unwrap();
+            let j = g.idx(pair[1].0, pair[1].1).unwrap();
+            assert_eq!(from_start[i].unwrap() + to_goal[i].unwrap(), min);
+            assert_eq!(next[i], Some(j));
+        }
+    }
+
+    #[test]
+    fn char_maps_use_the_glyph_table() {
+        let g = parse_grid_chars(".5#\n 0 1 9 \n\n..#\n").unwrap();
+        assert_eq!((g.w, g.h), (3, 3));
+        assert_eq!(&g.cells[..3], &[0x00, 0x8D, 0xFF]);
+        assert_eq!(&g.cells[3..6], &[0x00, 0x1C, 0xFF]);
+        assert!(validate_grid(&g).is_ok());
+
+        let err = parse_grid_chars(".x#").unwrap_err();
+        assert!(err.contains("'x'"), "{err}");
+        assert!(parse_grid_chars("..\n.").is_err());
+    }
+
+    #[test]
+    fn widest_path_keeps_to_high_ground() {
+        // Terrain bas (10) partout, sauf une crête (80+) par le haut et la droite
+        let g = grid(
+            "00 90 A0 B0\n\
+             10 10 10 80\n\
+             10 10 10 FF\n",
+        );
+        let (width, path) = widest_path(&g).unwrap();
+        assert_eq!(width, 0x80);
+        assert_eq!(path, vec![(0, 0), (1, 0), (2, 0), (3, 0), (3, 1), (3, 2)]);
+        assert_eq!(check_path(&g, &path), Ok(path_cost(&g, &path)));
+
+        // Un seul pas: seule l'arrivée compte
+        let (width, _) = widest_path(&grid("00 FF")).unwrap();
+        assert_eq!(width, 0xFF);
+    }
+
+    #[test]
+    fn time_limit_aborts_every_solver() {
+        let mut g = generate_grid(200, 200);
+        g.opts.time_limit = Some(Duration::ZERO);
+        let timed_out = |r: Result<(u64, Vec<(usize, usize)>), SolveError>| {
+            r.unwrap_err() == SolveError::TimeLimit
+        };
+        assert!(timed_out(dijkstra_min_cost(&g)));
+        assert!(timed_out(try_max_cost_among_shortest_paths(&g, None)));
+        assert!(timed_out(min_cost_min_turns(&g)));
+        assert_eq!(distance_map(&g).unwrap_err(), SolveError::TimeLimit);
+        assert_eq!(goal_distance_map(&g).unwrap_err(), SolveError::TimeLimit);
+        assert_eq!(widest_path(&g).unwrap_err(), SolveError::TimeLimit);
+        assert!(max_cost_among_shortest_paths(&g).is_none());
+
+        // Budget large: même résultat que sans limite
+        let mut small = grid("00 01 02\n03 04 05\n06 07 FF\n");
+        let expected = dijkstra_min_cost(&small).unwrap();
+        small.opts.time_limit = Some(Duration::from_secs(60));
+        assert_eq!(dijkstra_min_cost(&small).unwrap(), expected);
+    }
+
+    #[test]
+    fn direction_costs_reshape_the_optimum() {
+        let mut g = grid("00 01\n05 FF\n");
+        assert_eq!(
+            dijkstra_min_cost(&g).unwrap().1,
+            vec![(0, 0), (1, 0), (1, 1)]
+        );
+
+        // Descendre coûte 10x: mieux vaut descendre sur la case 05 que sur FF
+        g.opts.dir_cost = [1, 10, 1, 1];
+        let (cost, path) = dijkstra_min_cost(&g).unwrap();
+        assert_eq!(path, vec![(0, 0), (0, 1), (1, 1)]);
+        assert_eq!(cost, 5 * 10 + 0xFF);
+        assert_eq!(path_cost(&g, &path), cost);
+        assert_eq!(goal_distance_map(&g).unwrap().0[0], Some(cost));
+        assert_eq!(min_cost_min_turns(&g).unwrap().0, cost);
+    }
+
+    #[test]
+    fn single_cell_grid_is_its_own_goal() {
+        for text in ["00", "FF"] {
+            let g = grid(text);
+            assert!(validate_grid(&g).is_ok(), "{text}");
+            let trivial = (0, vec![(0, 0)]);
+            assert_eq!(dijkstra_min_cost(&g).unwrap(), trivial);
+            assert_eq!(max_cost_among_shortest_paths(&g).unwrap(), trivial);
+            assert_eq!(min_cost_min_turns(&g).unwrap(), trivial);
+            assert_eq!(check_path(&g, &[(0, 0)]).unwrap(), 0);
+            assert!(path_directions(&trivial.1).is_empty());
+        }
+        assert!(validate_grid(&grid("7F")).is_err());
+    }
+
+    #[test]
+    fn one_row_and_one_column_grids() {
+        let row = grid("00 05 FF");
+        assert_eq!(dijkstra_min_cost(&row).unwrap().0, 0x05 + 0xFF);
+        let col = grid("00\n05\nFF");
+        assert_eq!(max_cost_among_shortest_paths(&col).unwrap().0, 0x05 + 0xFF);
+    }
+
+    #[test]
+    fn braille_preview_packs_and_downscales() {
+        // Un point par case: (1,0), (0,1) et (1,3) donnent 0x08 | 0x02 | 0x80
+        let g = grid("00 FF\nFF 00\n00 00\n00 FF");
+        assert_eq!(braille_preview(&g, 1, 0x7F, None), vec!["\u{288A}"]);
+
+        // Blocs 2x2: seul le bloc haut-gauche a une moyenne au-dessus du seuil
+        let g = grid("FF FF 00 00\nFF 00 00 00\n00 00 00 00\n00 00 00 FF");
+        assert_eq!(braille_preview(&g, 2, 0x7F, None), vec!["\u{2801}"]);
+        // Le chemin allume les blocs qu'il traverse
+        let path = [(0, 0), (0, 1), (0, 2), (1, 2), (2, 2), (3, 2), (3, 3)];
+        assert_eq!(braille_preview(&g, 2, 0x7F, Some(&path)), vec!["\u{2813}"]);
+
+        assert_eq!(preview_scale(&generate_grid(512, 512), 64), 4);
+        assert_eq!(preview_scale(&g, 64), 1);
+    }
+
+    #[test]
+    fn distance_image_normalizes_and_blacks_out_walls() {
+        let g = grid("00 10\n20 FF\n");
+        let img = distance_heat_ppm(&g, &distance_map(&g).unwrap());
+        let header = b"P6\n2 2\n255\n";
+        assert_eq!(&img[..header.len()], header);
+        let px = &img[header.len()..];
+        assert_eq!(px.len(), 12);
+        assert_eq!(&px[..3], &[64, 0, 0]); // départ: distance 0
+        assert_eq!(&px[9..], &[255, 255, 255]); // arrivée: la plus lointaine
+
+        let mut walled = grid("00 FF\nFF FF\n");
+        walled.opts.max_cell = Some(0x80);
+        let img = distance_heat_ppm(&walled, &distance_map(&walled).unwrap());
+        let px = &img[header.len()..];
+        assert_eq!(&px[..3], &[255, 255, 255]);
+        assert!(px[3..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn one_sweep_answers_every_goal() {
+        let mut g = grid("00 05 01\n02 FF 01\n03 04 FF\n");
+        g.opts.max_cell = Some(0x80);
+        let res = multi_goal_costs(&g, &[(2, 2), (1, 1), (0, 2), (2, 0)]).unwrap();
+        // (1,1) vaut FF: au-dessus de --max-cell, donc inaccessible
+        assert_eq!(res.costs, vec![Some(7 + 0xFF), None, Some(5), Some(6)]);
+        assert_eq!(res.best, Some((2, vec![(0, 0), (0, 1), (0, 2)])));
+        assert_eq!(res.costs[0], Some(dijkstra_min_cost(&g).unwrap().0));
+
+        assert!(multi_goal_costs(&g, &[(3, 0)]).is_err());
+        assert_eq!(multi_goal_costs(&g, &[(1, 1)]).unwrap().best, None);
+    }
+
+    #[test]
+    fn farthest_cell_is_the_costliest_reachable_one() {
+        let far = |g: &Grid| {
+            let f = farthest_cell(g).unwrap();
+            (f.cell, f.cost, f.path)
+        };
+        let mut g = grid("00 05 01\n02 FF 01\n03 04 FF\n");
+        let (cell, cost, _) = far(&g);
+        assert_eq!((cell, cost), ((2, 2), 7 + 0xFF));
+
+        // (1,1) au-dessus de --max-cell, voisins de l'arrivée évités: (2,0) devient le pire
+        g.opts.max_cell = Some(0x80);
+        g.opts.avoid = HashSet::from([(2, 1), (1, 2)]);
+        assert_eq!(far(&g), ((2, 0), 6, vec![(0, 0), (1, 0), (2, 0)]));
+
+        // Égalité entre (1,0) et (0,1): la première en ordre ligne par ligne l'emporte
+        let mut tie = grid("00 04 01\n04 01 FF\n");
+        tie.opts.avoid = HashSet::from([(2, 0), (1, 1)]);
+        assert_eq!(far(&tie).0, (1, 0));
+
+        assert_eq!(far(&grid("00")), ((0, 0), 0, vec![(0, 0)]));
+    }
+
+    #[test]
+    fn signed_mode_takes_bonus_cells() {
+        // FB = -5 entouré de voisins >= 5 et l'arrivée FF = -1: aucun cycle négatif,
+        // le bonus bat le bord gauche
+        let g = grid("00 08 FB 08\n09 0F 0F 08\n09 09 09 FF\n");
+        assert!(validate_grid(&g).is_ok());
+        let (cost, path) = bellman_ford_min_cost(&g).unwrap();
+        assert_eq!(cost, 8 - 5 + 8 + 8 - 1);
+        assert_eq!(path, vec![(0, 0), (1, 0), (2, 0), (3, 0), (3, 1), (3, 2)]);
+        assert_eq!(dijkstra_min_cost(&g).unwrap().0, 9 + 9 + 9 + 9 + 0xFF);
+
+        // Le bonus F6 (-10) ne s'atteint qu'en remontant: il faut les quatre directions
+        let g = grid("00 7F F6 0A 0A\n0A 0A 0A 7F 0A\n7F 7F 7F 7F FF\n");
+        let (cost, path) = bellman_ford_min_cost(&g).unwrap();
+        assert_eq!(cost, 10 + 10 + 10 - 10 + 10 + 10 + 10 - 1);
+        assert_eq!(
+            path,
+            vec![
+                (0, 0),
+                (0, 1),
+                (1, 1),
+                (2, 1),
+                (2, 0),
+                (3, 0),
+                (4, 0),
+                (4, 1),
+                (4, 2)
+            ]
+        );
+
+        let mut walled = grid("00 80\n80 FF\n");
+        walled.opts.avoid = HashSet::from([(1, 0), (0, 1)]);
+        assert_eq!(
+            bellman_ford_min_cost(&walled).unwrap_err(),
+            SolveError::NoPath
+        );
+    }
+
+    #[test]
+    fn signed_mode_reports_negative_cycles() {
+        // FB (-5) et son voisin 02: aller-retour à -3, le minimum n'existe pas
+        let g = grid("00 02 FB\n01 09 09\n09 09 FF\n");
+        assert_eq!(
+            bellman_ford_min_cost(&g).unwrap_err(),
+            SolveError::NegativeCycle
+        );
+
+        // Même cycle hors d'atteinte derrière --avoid: résolu normalement
+        let mut cut = g.clone();
+        cut.opts.avoid = HashSet::from([(1, 0), (2, 1)]);
+        let (cost, _) = bellman_ford_min_cost(&cut).unwrap();
+        assert_eq!(cost, 1 + 9 + 9 - 1);
+    }
+
+    #[test]
+    fn signed_mode_solves_generated_maps() {
+        use rand::SeedableRng;
+
+        // Sans bonus les coûts signés sont ceux de Dijkstra; avec, chemin cohérent ou cycle négatif
+        for seed in 0..6 {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let mut g = generate_grid_with(10, 10, Distribution::Uniform, &mut rng);
+            assert!(validate_grid(&g).is_ok());
+            match bellman_ford_min_cost(&g) {
+                Ok((cost, path)) => {
+                    let sum: i64 = path[1..]
+                        .iter()
+                        .map(|&(x, y)| g.at(x, y).unwrap() as i8 as i64)
+                        .sum();
+                    assert_eq!(cost, sum, "seed {seed}");
+                }
+                Err(e) => assert_eq!(e, SolveError::NegativeCycle, "seed {seed}"),
+            }
+
+            for c in &mut g.cells {
+                *c &= 0x7F;
+            }
+            let (cost, _) = bellman_ford_min_cost(&g).unwrap();
+            assert_eq!(cost, dijkstra_min_cost(&g).unwrap().0 as i64, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn observer_sees_every_settled_cell_up_to_the_goal() {
+        let g = grid("00 05 01\n02 FF 01\n03 04 FF\n");
+        let mut seen = Vec::new();
+        let res = dijkstra_min_cost_observed(&g, |n, cell| seen.push((n, cell))).unwrap();
+        assert_eq!(res, dijkstra_min_cost(&g).unwrap());
+        // Compteur 1, 2, 3... depuis le départ, et l'arrivée en dernier
+        assert!(seen.iter().enumerate().all(|(i, &(n, _))| n == i + 1));
+        assert_eq!(seen.first().unwrap().1, (0, 0));
+        assert_eq!(seen.last().unwrap().1, (2, 2));
+        assert_eq!(seen.len(), 9);
+    }
+
+    #[test]
+    fn parse_rejects_bad_maps() {
+        assert!(parse_grid_text("").is_err());
+        assert!(parse_grid_text("00 01\n02\n").is_err());
+        assert!(parse_grid_text("00 GG").is_err());
+        assert!(validate_grid(&grid("01 FF")).is_err());
+    }
+}
@@ -1,13 +1,21 @@
 use clap::Parser;
-use rand::RngCore;
-use std::cmp::Ordering;
-use std::collections::{BinaryHeap, VecDeque};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rust_04::{
+    Band, Distribution, Grid, Profile, SolveError, SolveOptions, bellman_ford_min_cost,
+    braille_preview, carve_monotone_path, check_path, check_simple_path, count_turns, diff_grids,
+    dijkstra_min_cost, dijkstra_min_cost_observed, dijkstra_min_cost_profiled, distance_heat_ppm,
+    distance_map, farthest_cell, format_grid, generate_grid_with, goal_distance_map, greedy_walk,
+    min_cost_min_turns, min_cost_min_turns_profiled, multi_goal_costs, parse_grid_chars,
+    parse_grid_reader, parse_grid_text, parse_path_text, parse_wh, path_cost, path_directions,
+    preview_scale, read_grid_bin, try_max_cost_among_shortest_paths, validate_grid, widest_path,
+    write_grid_bin,
+};
+use std::collections::VecDeque;
 use std::fs;
-use std::io::{self, IsTerminal};
+use std::io::{self, IsTerminal, Write};
 use std::path::{Path, PathBuf};
-
-const MAX_SIDE: usize = 512;
-const MAX_CELLS: usize = MAX_SIDE * MAX_SIDE;
+use std::time::{Duration, Instant};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -20,14 +28,66 @@ struct Cli {
     #[arg(long = "generate", value_name = "WxH")]
     generate: Option<String>,
 
+    /// Cell distribution for --generate: uniform, gradient or clustered
+    #[arg(long = "distribution", value_name = "MODE", default_value = "uniform")]
+    distribution: Distribution,
+
+    /// Seed for --generate (reproducible maps)
+    #[arg(long = "seed", value_name = "N")]
+    seed: Option<u64>,
+
+    /// With --generate, carve a guaranteed passable right/down path (honors --max-cell)
+    #[arg(long = "ensure-solvable", requires = "generate")]
+    ensure_solvable: bool,
+
     /// Save generated map to file
     #[arg(long = "output", value_name = "FILE")]
     output: Option<PathBuf>,
 
+    /// Treat cells with a value above HEX as impassable (start and goal excepted)
+    #[arg(long = "max-cell", value_name = "HEX", value_parser = parse_hex_u8)]
+    max_cell: Option<u8>,
+
+    /// Forbid the cell at X,Y for every search, as if it were an obstacle (repeatable)
+    #[arg(long = "avoid", value_name = "X,Y", value_parser = parse_xy)]
+    avoid: Vec<(usize, usize)>,
+
+    /// Compare with another map of the same size: differing cells and min cost change
+    #[arg(long = "compare", value_name = "OTHER")]
+    compare: Option<PathBuf>,
+
+    /// Save the map (generated or loaded) in the binary .grid format
+    #[arg(long = "save-bin", value_name = "PATH")]
+    save_bin: Option<PathBuf>,
+
+    /// Load the map from a binary .grid file instead of MAP_FILE
+    #[arg(long = "load-bin", value_name = "PATH", conflicts_with_all = ["map_file", "generate"])]
+    load_bin: Option<PathBuf>,
+
+    /// Solve on the inverted map (each cell becomes FF - value), after corner validation
+    #[arg(long = "invert", conflicts_with = "compare")]
+    invert: bool,
+
+    /// Read MAP_FILE (and --compare) as one character per cell: . = 00, # = FF, 0-9 scaled to 00-FF
+    #[arg(long = "charset", conflicts_with_all = ["load_bin", "generate"])]
+    charset: bool,
+
     /// Show colored map
     #[arg(long = "visualize")]
     visualize: bool,
 
+    /// Color scheme for --visualize on a terminal: rainbow, grayscale or heat
+    #[arg(long = "palette", value_name = "MODE", default_value = "rainbow")]
+    palette: Palette,
+
+    /// Show a downscaled Braille preview of the map (fits large grids on screen)
+    #[arg(long = "preview")]
+    preview: bool,
+
+    /// With --preview, set the dots crossed by the min path
+    #[arg(long = "preview-path", requires = "preview")]
+    preview_path: bool,
+
     /// Show both min and max paths
     #[arg(long = "both")]
     both: bool,
@@ -36,10 +96,181 @@ struct Cli {
     #[arg(long = "animate")]
     animate: bool,
 
+    /// Print the min cost to reach every cell instead of a single path
+    #[arg(long = "distance-map")]
+    distance_map: bool,
+
+    /// Write the min cost to reach every cell as a heatmap image (binary PPM, 1 pixel per cell)
+    #[arg(long = "dist-image", value_name = "PATH")]
+    dist_image: Option<PathBuf>,
+
+    /// Emit JSON output (with --distance-map)
+    #[arg(long = "json", requires = "distance_map")]
+    json: bool,
+
+    /// Write the min path's step-by-step costs to PATH as a JSON array
+    #[arg(
+        long = "trace-json",
+        value_name = "PATH",
+        conflicts_with_all = [
+            "quiet", "check_path", "compare", "inspect", "widest_path", "distance_map", "dist_image"
+        ]
+    )]
+    trace_json: Option<PathBuf>,
+
+    /// Write the min path to PATH as CSV, one row per cell (with --both, max rows too)
+    #[arg(
+        long = "path-csv",
+        value_name = "PATH",
+        conflicts_with_all = [
+            "quiet", "check_path", "compare", "inspect", "widest_path", "distance_map", "dist_image",
+            "goals", "repeat_solve"
+        ]
+    )]
+    path_csv: Option<PathBuf>,
+
+    /// Print the min path as turn-by-turn directions
+    #[arg(long = "directions")]
+    directions: bool,
+
+    /// Verify a path file (one x,y per line) and report its cost
+    #[arg(long = "check-path", value_name = "FILE")]
+    check_path: Option<PathBuf>,
+
+    /// Describe one cell: value, costs from start and to goal, next hop, neighbors (repeatable)
+    #[arg(long = "inspect", value_name = "X,Y", value_parser = parse_xy)]
+    inspect: Vec<(usize, usize)>,
+
+    /// Report the min cost to each goal of "X,Y;X,Y;..." from one search, and the path to the cheapest
+    #[arg(
+        long = "goals",
+        value_name = "X,Y;...",
+        value_parser = parse_goals,
+        conflicts_with_all = [
+            "check_path", "compare", "inspect", "widest_path", "distance_map", "dist_image",
+            "quiet", "both", "trace_json", "path_csv"
+        ]
+    )]
+    goals: Option<Goals>,
+
+    /// Route to the reachable cell with the highest min cost instead of the goal
+    #[arg(
+        long = "farthest",
+        conflicts_with_all = [
+            "check_path", "compare", "goals", "inspect", "widest_path", "distance_map",
+            "dist_image", "quiet", "both", "trace_json", "path_csv", "repeat_solve"
+        ]
+    )]
+    farthest: bool,
+
+    /// Read cells as signed bytes: 0x80-0xFF become bonuses (-128..-1); fails on a negative cycle
+    #[arg(
+        long = "signed",
+        conflicts_with_all = [
+            "check_path", "compare", "goals", "farthest", "inspect", "widest_path",
+            "distance_map", "dist_image", "quiet", "both", "trace_json", "path_csv",
+            "repeat_solve", "band", "min_turns", "animate", "visualize", "preview",
+            "baseline", "verbose_steps"
+        ]
+    )]
+    signed: bool,
+
+    /// Solve the min-cost path N times, check every run agrees and report the median time
+    #[arg(
+        long = "repeat-solve",
+        value_name = "N",
+        conflicts_with_all = [
+            "check_path", "compare", "goals", "inspect", "widest_path", "distance_map",
+            "dist_image", "quiet", "profile"
+        ]
+    )]
+    repeat_solve: Option<usize>,
+
+    /// Find the path whose lowest cell is as high as possible (maximin) instead of min/max cost
+    #[arg(
+        long = "widest-path",
+        conflicts_with_all = [
+            "check_path", "compare", "inspect", "distance_map", "dist_image", "quiet", "both"
+        ]
+    )]
+    widest_path: bool,
+
+    /// Among min-cost paths, pick the one with the fewest turns
+    #[arg(long = "min-turns")]
+    min_turns: bool,
+
+    /// Multiply costs of cells in [LO, HI] by MULT (LO:HI:MULT, hex bounds, repeatable)
+    #[arg(long = "band", value_name = "LO:HI:MULT")]
+    band: Vec<Band>,
+
+    /// Multiply the cost of cells entered by an upward move by N
+    #[arg(long = "cost-up", value_name = "N", default_value_t = 1)]
+    cost_up: u64,
+
+    /// Multiply the cost of cells entered by a downward move by N
+    #[arg(long = "cost-down", value_name = "N", default_value_t = 1)]
+    cost_down: u64,
+
+    /// Multiply the cost of cells entered by a leftward move by N
+    #[arg(long = "cost-left", value_name = "N", default_value_t = 1)]
+    cost_left: u64,
+
+    /// Multiply the cost of cells entered by a rightward move by N
+    #[arg(long = "cost-right", value_name = "N", default_value_t = 1)]
+    cost_right: u64,
+
+    /// Assert that reported paths never revisit a cell
+    #[arg(long = "simple-check")]
+    simple_check: bool,
+
+    /// Also run a greedy walk and compare its cost with the optimum
+    #[arg(long = "baseline")]
+    baseline: bool,
+
+    /// Print only the min cost in decimal (and the max cost on a second line with --both)
+    #[arg(
+        long = "quiet",
+        conflicts_with_all = [
+            "generate", "visualize", "preview", "animate", "distance_map", "dist_image", "directions",
+            "check_path", "baseline", "verbose_steps"
+        ]
+    )]
+    quiet: bool,
+
+    /// Abort a solve that takes longer than MS milliseconds (exit code 1)
+    #[arg(long = "time-limit", value_name = "MS")]
+    time_limit: Option<u64>,
+
+    /// Print solver counters and per-phase timings to stderr
+    #[arg(long = "profile")]
+    profile: bool,
+
+    /// Show cumulative cost percentage for each step
+    #[arg(long = "verbose-steps")]
+    verbose_steps: bool,
+
     /// Map file (hex values, space separated)
     map_file: Option<PathBuf>,
 }
 
+impl Cli {
+    // Réglages des solveurs; multiplicateurs dans l'ordre de `Direction` (haut, bas, gauche, droite)
+    fn solve_options(&self) -> SolveOptions {
+        SolveOptions {
+            bands: self.band.clone(),
+            max_cell: self.max_cell,
+            avoid: self.avoid.iter().copied().collect(),
+            time_limit: self.time_limit.map(Duration::from_millis),
+            dir_cost: [
+                self.cost_up,
+                self.cost_down,
+                self.cost_left,
+                self.cost_right,
+            ],
+        }
+    }
+}
+
 fn main() {
     let cli = Cli::parse();
 
@@ -63,8 +294,50 @@ enum Exit {
     Runtime(String),
 }
 
+impl From<SolveError> for Exit {
+    fn from(e: SolveError) -> Self {
+        Exit::Runtime(e.to_string())
+    }
+}
+
 /*CLI / ENTRY*/
 
+fn parse_hex_u8(raw: &str) -> Result<u8, String> {
+    let s = raw.trim();
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u8::from_str_radix(hex, 16).map_err(|_| format!("invalid hex byte '{raw}' (expected 00-FF)"))
+}
+
+fn parse_xy(raw: &str) -> Result<(usize, usize), String> {
+    let (xs, ys) = raw
+        .split_once(',')
+        .ok_or_else(|| format!("invalid cell '{raw}' (expected X,Y, e.g. 3,1)"))?;
+    let coord = |t: &str| {
+        t.trim()
+            .parse::<usize>()
+            .map_err(|_| format!("invalid cell '{raw}' (expected X,Y, e.g. 3,1)"))
+    };
+    Ok((coord(xs)?, coord(ys)?))
+}
+
+// Vec dans un alias: clap traiterait un Vec<...> nu comme une option répétable
+type Goals = Vec<(usize, usize)>;
+
+fn parse_goals(raw: &str) -> Result<Goals, String> {
+    let goals = raw
+        .split(';')
+        .filter(|part| !part.trim().is_empty())
+        .map(parse_xy)
+        .collect::<Result<Goals, _>>()?;
+    if goals.is_empty() {
+        return Err("expected at least one X,Y goal".to_string());
+    }
+    Ok(goals)
+}
+
 fn entry(cli: Cli) -> Result<(), Exit> {
     // Validation des combinaisons d’options
     if cli.generate.is_some() && cli.map_file.is_some() {
@@ -72,9 +345,9 @@ fn entry(cli: Cli) -> Result<(), Exit> {
             "cannot use MAP_FILE together with --generate".to_string(),
         ));
     }
-    if cli.generate.is_none() && cli.map_file.is_none() {
+    if cli.generate.is_none() && cli.map_file.is_none() && cli.load_bin.is_none() {
         return Err(Exit::Cli(
-            "missing input: provide MAP_FILE or use --generate WxH".to_string(),
+            "missing input: provide MAP_FILE, --load-bin PATH or --generate WxH".to_string(),
         ));
     }
     if cli.output.is_some() && cli.generate.is_none() {
@@ -84,7 +357,17 @@ fn entry(cli: Cli) -> Result<(), Exit> {
     // Génération map aléatoire
     if let Some(spec) = cli.generate.as_deref() {
         let (w, h) = parse_wh(spec).map_err(Exit::Cli)?;
-        let grid = generate_grid(w, h);
+        let mut rng = match cli.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let mut grid = generate_grid_with(w, h, cli.distribution, &mut rng);
+        if cli.ensure_solvable {
+            let limit = cli.max_cell.unwrap_or(0xFF);
+            carve_monotone_path(&mut grid, limit, &mut rng);
+            // Sur stderr pour ne pas polluer la map imprimée
+            eprintln!("Note: a solvable path was guaranteed (cells <= 0x{limit:02X})");
+        }
 
         if let Some(path) = cli.output.as_deref() {
             write_grid_file(path, &grid).map_err(Exit::Runtime)?;
@@ -93,28 +376,148 @@ fn entry(cli: Cli) -> Result<(), Exit> {
         } else {
             println!("{}", format_grid(&grid));
         }
+        if let Some(path) = cli.save_bin.as_deref() {
+            write_grid_bin_file(path, &grid).map_err(Exit::Runtime)?;
+            println!("Binary map saved to: {}", path.display());
+        }
 
         // Si on demande en plus une analyse/visualisation sur la map générée
-        if cli.visualize || cli.both || cli.animate {
-            analyze_and_print(&grid, cli.visualize, cli.both, cli.animate)?;
+        if cli.visualize
+            || cli.preview
+            || cli.both
+            || cli.animate
+            || cli.distance_map
+            || cli.dist_image.is_some()
+        {
+            grid.opts = cli.solve_options();
+            analyze_and_print(&grid, &cli)?;
         }
         return Ok(());
     }
 
-    // Analyse fichier existant
-    let path = cli.map_file.as_ref().expect("validated");
-    let content = fs::read_to_string(path)
+    // Analyse fichier existant (texte ou binaire)
+    let (path, binary) = match (&cli.load_bin, &cli.map_file) {
+        (Some(p), _) => (p, true),
+        (None, Some(p)) => (p, false),
+        (None, None) => unreachable!("validated"),
+    };
+    let file = fs::File::open(path)
         .map_err(|e| Exit::Runtime(format!("failed to read '{}': {e}", path.display())))?;
-    let grid = parse_grid_text(&content).map_err(Exit::Cli)?;
+    let reader = io::BufReader::new(file);
+    let mut grid = if binary {
+        read_grid_bin(reader).map_err(|e| Exit::Cli(format!("'{}': {e}", path.display())))?
+    } else if cli.charset {
+        let grid = read_char_map(path)?;
+        // Les coins doivent déjà valoir 00/FF: on rappelle les caractères possibles
+        validate_grid(&grid).map_err(|e| {
+            Exit::Cli(format!(
+                "{e} (use '.' or '0' for the start, '#' or '9' for the goal)"
+            ))
+        })?;
+        grid
+    } else {
+        parse_grid_reader(reader).map_err(Exit::Cli)?
+    };
+
+    // Conversion seule: pas d'analyse
+    if let Some(out) = cli.save_bin.as_deref() {
+        write_grid_bin_file(out, &grid).map_err(Exit::Runtime)?;
+        println!("Binary map saved to: {}", out.display());
+        return Ok(());
+    }
 
-    analyze_and_print(&grid, cli.visualize, cli.both, cli.animate)
+    grid.opts = cli.solve_options();
+
+    analyze_and_print(&grid, &cli)
 }
 
-fn analyze_and_print(grid: &Grid, visualize: bool, both: bool, animate: bool) -> Result<(), Exit> {
+fn analyze_and_print(grid: &Grid, cli: &Cli) -> Result<(), Exit> {
     validate_grid(grid).map_err(Exit::Cli)?;
+    for &(x, y) in &cli.avoid {
+        if grid.idx(x, y).is_none() {
+            return Err(Exit::Cli(format!(
+                "--avoid {x},{y} is outside the {}x{} grid",
+                grid.w, grid.h
+            )));
+        }
+        if (x, y) == (0, 0) || (x, y) == (grid.w - 1, grid.h - 1) {
+            return Err(Exit::Cli(format!(
+                "--avoid {x},{y} is the start or the goal"
+            )));
+        }
+    }
+
+    // Inversion après validation: les coins deviennent FF (départ) et 00 (arrivée)
+    let inverted;
+    let grid = if cli.invert {
+        inverted = grid.inverted();
+        &inverted
+    } else {
+        grid
+    };
+
+    if let Some(path_file) = cli.check_path.as_deref() {
+        return run_check_path(grid, path_file);
+    }
+
+    if let Some(other) = cli.compare.as_deref() {
+        return run_compare(grid, other, cli.charset);
+    }
+
+    if let Some(goals) = cli.goals.as_deref() {
+        return run_goals(grid, goals, cli);
+    }
+
+    if cli.farthest {
+        return run_farthest(grid, cli);
+    }
+
+    if cli.signed {
+        return run_signed(grid, cli);
+    }
+
+    if let Some(runs) = cli.repeat_solve {
+        return run_repeat_solve(grid, runs, cli);
+    }
+
+    if !cli.inspect.is_empty() {
+        return run_inspect(grid, &cli.inspect);
+    }
+
+    if cli.widest_path {
+        return run_widest_path(grid, cli);
+    }
+
+    if cli.distance_map || cli.dist_image.is_some() {
+        let dist = distance_map(grid)?;
+        if cli.json {
+            println!("{}", format_distance_json(grid, &dist));
+        } else if cli.distance_map {
+            println!("DISTANCE MAP (min cost from (0,0), -- = unreachable):");
+            println!("{}", format_distance_grid(grid, &dist));
+        }
+        if let Some(path) = cli.dist_image.as_deref() {
+            fs::write(path, distance_heat_ppm(grid, &dist))
+                .map_err(|e| Exit::Runtime(format!("failed to write '{}': {e}", path.display())))?;
+            // Sur stderr avec --json pour garder une sortie JSON pure
+            if cli.json {
+                eprintln!("Distance image saved to: {}", path.display());
+            } else {
+                println!("Distance image saved to: {}", path.display());
+            }
+        }
+        return Ok(());
+    }
+
+    if cli.quiet {
+        return print_costs_only(grid, cli);
+    }
 
     println!("Analyzing hexadecimal grid...");
     println!("Grid size: {}x{}", grid.w, grid.h);
+    if cli.invert {
+        println!("Grid inverted: each cell is FF - value (corners checked before inversion)");
+    }
     println!("Start: (0,0) = 0x{:02X}", grid.at(0, 0).unwrap_or(0));
     println!(
         "End: ({},{}) = 0x{:02X}",
@@ -122,369 +525,537 @@ fn analyze_and_print(grid: &Grid, visualize: bool, both: bool, animate: bool) ->
         grid.h - 1,
         grid.at(grid.w - 1, grid.h - 1).unwrap_or(0)
     );
+    for b in &grid.opts.bands {
+        println!("Band: 0x{:02X}-0x{:02X} x{}", b.lo, b.hi, b.mult);
+    }
+    if grid.opts.dir_cost != [1; 4] {
+        let [up, down, left, right] = grid.opts.dir_cost;
+        println!("Direction costs: up x{up}, down x{down}, left x{left}, right x{right}");
+    }
+    if !grid.opts.avoid.is_empty() {
+        let mut avoided: Vec<_> = grid.opts.avoid.iter().collect();
+        avoided.sort_by_key(|&&(x, y)| (y, x));
+        let cells: Vec<String> = avoided.iter().map(|(x, y)| format!("({x},{y})")).collect();
+        println!("Avoided cells ({}): {}", cells.len(), cells.join(" "));
+    }
+    if let Some(limit) = grid.opts.max_cell {
+        println!(
+            "Excluded cells (> 0x{:02X}): {}",
+            limit,
+            grid.excluded_cells()
+        );
+    }
     println!();
 
-    // Chemin de coût minimal (Dijkstra)
-    let (min_cost, min_path) = dijkstra_min_cost(grid).map_err(Exit::Runtime)?;
+    let mut prof = cli.profile.then(Profile::default);
+    let (min_cost, min_path) = solve_min(grid, cli, prof.as_mut())?;
+
+    if cli.simple_check {
+        ensure_simple(&min_path, "minimum")?;
+    }
 
     println!("MINIMUM COST PATH:");
-    print_path_report(grid, min_cost, &min_path);
+    print_path_report(grid, min_cost, &min_path, cli.verbose_steps);
+    if cli.min_turns {
+        println!("Turns: {}", count_turns(&min_path));
+    }
+    if let Some(path) = cli.trace_json.as_deref() {
+        write_atomic(path, format_trace_json(grid, &min_path).as_bytes()).map_err(Exit::Runtime)?;
+        println!("Trace saved to: {}", path.display());
+    }
+
+    if cli.directions {
+        println!();
+        print_directions(&min_path);
+    }
+
+    if cli.baseline {
+        println!();
+        print_baseline(grid, min_cost)?;
+    }
 
     // Chemin de coût maximal parmi les chemins à nb de pas minimal
-    let max_res = if both {
-        max_cost_among_shortest_paths(grid)
+    let max_res = if cli.both {
+        solve_max(grid, prof.as_mut())?
     } else {
         None
     };
 
-    if both {
+    if cli.simple_check
+        && let Some((_, ref max_path)) = max_res
+    {
+        ensure_simple(max_path, "maximum")?;
+    }
+
+    if cli.both {
         println!();
         println!("MAXIMUM COST PATH:");
         if let Some((max_cost, ref max_path)) = max_res {
-            print_path_report(grid, max_cost, max_path);
+            print_path_report(grid, max_cost, max_path, cli.verbose_steps);
         } else {
             println!("No path found.");
         }
     }
 
-    if visualize {
+    if let Some(path) = cli.path_csv.as_deref() {
+        let mut paths = vec![("min", min_path.as_slice())];
+        if let Some((_, ref max_path)) = max_res {
+            paths.push(("max", max_path.as_slice()));
+        }
+        write_atomic(path, format_path_csv(grid, &paths, cli.both).as_bytes())
+            .map_err(Exit::Runtime)?;
         println!();
-        let max_path_ref = max_res.as_ref().map(|(_, p)| p.as_slice());
-        print_visualization(grid, &min_path, max_path_ref);
+        println!("Path CSV saved to: {}", path.display());
     }
 
-    if animate {
+    if cli.visualize {
         println!();
-        run_light_animation(grid);
+        let max_path_ref = max_res.as_ref().map(|(_, p)| p.as_slice());
+        print_visualization(grid, &min_path, max_path_ref, cli.palette);
     }
 
-    Ok(())
-}
-
-/*GRID / PARSING*/
-
-#[derive(Clone, Debug)]
-struct Grid {
-    w: usize,
-    h: usize,
-    cells: Vec<u8>,
-}
+    if cli.preview {
+        println!();
+        print_preview(grid, cli.preview_path.then_some(min_path.as_slice()));
+    }
 
-impl Grid {
-    fn idx(&self, x: usize, y: usize) -> Option<usize> {
-        if x < self.w && y < self.h {
-            Some(y * self.w + x)
+    if cli.animate {
+        println!();
+        // Animation en direct seulement sur un terminal; sinon l'aperçu statique
+        if io::stdout().is_terminal() {
+            run_frontier_animation(grid);
         } else {
-            None
+            run_light_animation(grid);
         }
     }
 
-    fn at(&self, x: usize, y: usize) -> Option<u8> {
-        self.idx(x, y).and_then(|i| self.cells.get(i).copied())
-    }
-}
-
-fn parse_wh(s: &str) -> Result<(usize, usize), String> {
-    let s = s.trim();
-    let (w_s, h_s) = s
-        .split_once('x')
-        .or_else(|| s.split_once('X'))
-        .ok_or_else(|| format!("invalid size '{s}' (expected WxH, e.g. 10x10)"))?;
-    let w: usize = w_s
-        .trim()
-        .parse()
-        .map_err(|_| format!("invalid width in '{s}'"))?;
-    let h: usize = h_s
-        .trim()
-        .parse()
-        .map_err(|_| format!("invalid height in '{s}'"))?;
-    if w == 0 || h == 0 {
-        return Err("width and height must be > 0".to_string());
-    }
-    if w > MAX_SIDE || h > MAX_SIDE || w * h > MAX_CELLS {
-        return Err("grid too large".to_string());
+    if let Some(p) = &prof {
+        print_profile(p, cli.both);
     }
-    Ok((w, h))
-}
 
-fn generate_grid(w: usize, h: usize) -> Grid {
-    let mut rng = rand::thread_rng();
-    let mut cells = Vec::with_capacity(w * h);
-
-    for _ in 0..(w * h) {
-        let val = (rng.next_u32() & 0xFF) as u8;
-        cells.push(val);
-    }
-
-    // Contraintes : 00 (top-left), FF (bottom-right)
-    if let Some(first) = cells.first_mut() {
-        *first = 0x00;
-    }
-    if let Some(last) = cells.last_mut() {
-        *last = 0xFF;
-    }
-    Grid { w, h, cells }
+    Ok(())
 }
 
-fn write_grid_file(path: &Path, grid: &Grid) -> Result<(), String> {
-    let mut out = String::new();
-    for y in 0..grid.h {
-        for x in 0..grid.w {
-            if x > 0 {
-                out.push(' ');
+// Chemin de coût minimal (Dijkstra), départagé par le nombre de virages si demandé
+fn solve_min(
+    grid: &Grid,
+    cli: &Cli,
+    mut prof: Option<&mut Profile>,
+) -> Result<(u64, Vec<(usize, usize)>), Exit> {
+    let res = match (cli.min_turns, prof.as_deref_mut()) {
+        (true, Some(p)) => min_cost_min_turns_profiled(grid, p),
+        (true, None) => min_cost_min_turns(grid),
+        (false, Some(p)) => dijkstra_min_cost_profiled(grid, p),
+        (false, None) => dijkstra_min_cost(grid),
+    };
+    res.map_err(|e| {
+        if e == SolveError::TimeLimit {
+            // Compteurs partiels: où le temps est passé avant l'abandon
+            if let Some(p) = prof {
+                print_profile(p, false);
             }
-            let v = grid.at(x, y).unwrap_or(0);
-            out.push_str(&format!("{v:02X}"));
+            return e.into();
         }
-        out.push('\n');
-    }
-    fs::write(path, out).map_err(|e| format!("failed to write '{}': {e}", path.display()))
+        match (grid.opts.max_cell, grid.opts.avoid.is_empty()) {
+            (Some(limit), true) => Exit::Runtime(format!(
+                "{e}: the goal is cut off by cells above --max-cell 0x{limit:02X}"
+            )),
+            (Some(limit), false) => Exit::Runtime(format!(
+                "{e}: the goal is cut off by --avoid cells and cells above --max-cell 0x{limit:02X}"
+            )),
+            (None, false) => Exit::Runtime(format!("{e}: the goal is cut off by --avoid cells")),
+            (None, true) => e.into(),
+        }
+    })
 }
 
-fn format_grid(grid: &Grid) -> String {
-    let mut out = String::new();
-    for y in 0..grid.h {
-        for x in 0..grid.w {
-            if x > 0 {
-                out.push(' ');
+// Coût total et chemin, tels que renvoyés par les solveurs
+type Solved = (u64, Vec<(usize, usize)>);
+
+// Ok(None) quand le but est inaccessible; Err seulement si --time-limit est dépassé
+fn solve_max(grid: &Grid, mut prof: Option<&mut Profile>) -> Result<Option<Solved>, Exit> {
+    match try_max_cost_among_shortest_paths(grid, prof.as_deref_mut()) {
+        Ok(found) => Ok(Some(found)),
+        Err(SolveError::NoPath) => Ok(None),
+        Err(e) => {
+            if let Some(p) = prof {
+                print_profile(p, true);
             }
-            let v = grid.at(x, y).unwrap_or(0);
-            out.push_str(&format!("{v:02X}"));
-        }
-        if y + 1 < grid.h {
-            out.push('\n');
+            Err(e.into())
         }
     }
-    out
 }
 
-fn parse_grid_text(content: &str) -> Result<Grid, String> {
-    let mut rows: Vec<Vec<u8>> = Vec::new();
-
-    for line in content.lines() {
-        let line = line.trim();
-        if line.is_empty() {
-            continue;
-        }
-
-        let mut row = Vec::new();
-        for tok in line.split_whitespace() {
-            let t0 = tok.trim().trim_end_matches(',').trim_end_matches(';');
-            let t = t0
-                .strip_prefix("0x")
-                .or_else(|| t0.strip_prefix("0X"))
-                .unwrap_or(t0);
-
-            if t.is_empty() {
-                return Err("empty hex token".to_string());
-            }
-            if t.len() > 2 {
-                return Err(format!("invalid hex token '{tok}' (expected 00-FF)"));
-            }
-            let v = u8::from_str_radix(t, 16)
-                .map_err(|_| format!("invalid hex token '{tok}' (expected 00-FF)"))?;
-            row.push(v);
-        }
-        if !row.is_empty() {
-            rows.push(row);
-        }
+fn print_profile(p: &Profile, with_max: bool) {
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    eprintln!(
+        "[PROFILE] min solve: {:.3} ms ({} heap pops, {} relaxations)",
+        ms(p.min_solve),
+        p.heap_pops,
+        p.relaxations
+    );
+    if with_max {
+        eprintln!("[PROFILE] max BFS: {:.3} ms", ms(p.bfs));
+        eprintln!("[PROFILE] max DP: {:.3} ms", ms(p.dp));
     }
+}
 
-    if rows.is_empty() {
-        return Err("empty map".to_string());
+// Sortie pour scripts: uniquement les coûts, en décimal
+fn print_costs_only(grid: &Grid, cli: &Cli) -> Result<(), Exit> {
+    let mut prof = cli.profile.then(Profile::default);
+    let (min_cost, min_path) = solve_min(grid, cli, prof.as_mut())?;
+    if cli.simple_check {
+        ensure_simple(&min_path, "minimum")?;
     }
+    println!("{min_cost}");
 
-    let w = rows[0].len();
-    if w == 0 {
-        return Err("invalid map width".to_string());
-    }
-    if w > MAX_SIDE {
-        return Err("grid too wide".to_string());
-    }
-    for (i, r) in rows.iter().enumerate() {
-        if r.len() != w {
-            return Err(format!("non-rectangular map at row {i}"));
+    if cli.both {
+        let (max_cost, max_path) = solve_max(grid, prof.as_mut())?.ok_or(SolveError::NoPath)?;
+        if cli.simple_check {
+            ensure_simple(&max_path, "maximum")?;
         }
+        println!("{max_cost}");
     }
 
-    let h = rows.len();
-    if h > MAX_SIDE || w * h > MAX_CELLS {
-        return Err("grid too large".to_string());
+    if let Some(p) = &prof {
+        print_profile(p, cli.both);
     }
+    Ok(())
+}
+
+fn ensure_simple(path: &[(usize, usize)], which: &str) -> Result<(), Exit> {
+    check_simple_path(path)
+        .map_err(|e| Exit::Runtime(format!("{which} path is not simple: {e} (solver bug)")))
+}
 
-    let mut cells = Vec::with_capacity(w * h);
-    for r in rows {
-        cells.extend(r);
+fn run_compare(grid: &Grid, other_file: &Path, charset: bool) -> Result<(), Exit> {
+    let mut other = if charset {
+        read_char_map(other_file)?
+    } else {
+        let content = fs::read_to_string(other_file).map_err(|e| {
+            Exit::Runtime(format!("failed to read '{}': {e}", other_file.display()))
+        })?;
+        parse_grid_text(&content)
+            .map_err(|e| Exit::Cli(format!("'{}': {e}", other_file.display())))?
+    };
+    other.opts = grid.opts.clone();
+    validate_grid(&other).map_err(|e| Exit::Cli(format!("'{}': {e}", other_file.display())))?;
+
+    let diffs = diff_grids(grid, &other).map_err(Exit::Cli)?;
+    for &(x, y, a, b) in &diffs {
+        println!("({x},{y}): 0x{a:02X} vs 0x{b:02X}");
     }
 
-    Ok(Grid { w, h, cells })
+    let (cost_a, path_a) = dijkstra_min_cost(grid)?;
+    let (cost_b, path_b) = dijkstra_min_cost(&other)?;
+    if path_a == path_b {
+        println!("Min path: unchanged");
+    } else {
+        let moved = path_b.iter().filter(|c| !path_a.contains(c)).count();
+        println!("Min path: changed ({moved} cells not on the original path)");
+    }
+    println!(
+        "{} cells differ, min cost {} -> {} ({:+})",
+        diffs.len(),
+        cost_a,
+        cost_b,
+        cost_b as i64 - cost_a as i64
+    );
+    Ok(())
 }
 
-fn validate_grid(grid: &Grid) -> Result<(), String> {
-    if grid.w == 0 || grid.h == 0 {
-        return Err("invalid grid dimensions".to_string());
-    }
-    if grid.cells.len() != grid.w * grid.h {
-        return Err("invalid grid storage".to_string());
+fn run_goals(grid: &Grid, goals: &[(usize, usize)], cli: &Cli) -> Result<(), Exit> {
+    if let Some(&(x, y)) = goals.iter().find(|&&(x, y)| grid.idx(x, y).is_none()) {
+        return Err(Exit::Cli(format!(
+            "--goals {x},{y} is outside the {}x{} grid",
+            grid.w, grid.h
+        )));
     }
-    if grid.at(0, 0) != Some(0x00) {
-        return Err("start (top-left) must be 00".to_string());
+    let res = multi_goal_costs(grid, goals).map_err(Exit::Runtime)?;
+
+    println!("GOALS (min cost from (0,0)):");
+    for (&(x, y), cost) in goals.iter().zip(&res.costs) {
+        match cost {
+            Some(c) => println!("({x},{y}): 0x{c:X} ({c})"),
+            None => println!("({x},{y}): unreachable"),
+        }
     }
-    if grid.at(grid.w - 1, grid.h - 1) != Some(0xFF) {
-        return Err("end (bottom-right) must be FF".to_string());
+
+    let Some((n, path)) = res.best else {
+        println!();
+        println!("No goal is reachable.");
+        return Ok(());
+    };
+    let (x, y) = goals[n];
+    println!();
+    println!("CHEAPEST GOAL ({x},{y}):");
+    print_path_report(grid, res.costs[n].unwrap_or(0), &path, cli.verbose_steps);
+    if cli.directions {
+        println!();
+        print_directions(&path);
     }
     Ok(())
 }
 
-/*MIN COST (Dijkstra)*/
-
-#[derive(Copy, Clone, Eq, PartialEq)]
-struct State {
-    cost: u64,
-    idx: usize,
+// Pire cas de la carte: la case atteignable la plus chère depuis le départ
+fn run_farthest(grid: &Grid, cli: &Cli) -> Result<(), Exit> {
+    let far = farthest_cell(grid)?;
+    let (x, y) = far.cell;
+    println!(
+        "FARTHEST CELL ({x},{y}) = 0x{:02X}:",
+        grid.at(x, y).unwrap_or(0)
+    );
+    print_path_report(grid, far.cost, &far.path, cli.verbose_steps);
+    if cli.directions {
+        println!();
+        print_directions(&far.path);
+    }
+    Ok(())
 }
 
-impl Ord for State {
-    fn cmp(&self, other: &Self) -> Ordering {
-        other
-            .cost
-            .cmp(&self.cost)
-            .then_with(|| other.idx.cmp(&self.idx))
+// Coûts signés: Bellman-Ford (les bonus rendent Dijkstra faux), droite/bas pour éviter les cycles négatifs
+fn run_signed(grid: &Grid, cli: &Cli) -> Result<(), Exit> {
+    let (total, path) = bellman_ford_min_cost(grid)?;
+    println!("MINIMUM COST PATH (signed):");
+    println!("Total cost: {total} (signed)");
+    println!("Path length: {} steps", path.len());
+    println!();
+    println!("Step-by-step costs:");
+    println!("Start 0x{:02X} (0,0)", grid.at(0, 0).unwrap_or(0));
+    let mut acc = 0i64;
+    for w in path.windows(2) {
+        let (x, y) = w[1];
+        let step = grid.signed_step_weight(w[0], w[1]);
+        acc += step;
+        println!(
+            "+ 0x{:02X} ({step}) ({x},{y}) -> {acc}",
+            grid.at(x, y).unwrap_or(0)
+        );
     }
+    println!("Total: {total}");
+    if cli.directions {
+        println!();
+        print_directions(&path);
+    }
+    Ok(())
 }
 
-impl PartialOrd for State {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+// Auto-test de déterminisme: toutes les résolutions doivent donner le même coût
+// et le même chemin; la médiane des temps sert de micro-benchmark
+fn run_repeat_solve(grid: &Grid, runs: usize, cli: &Cli) -> Result<(), Exit> {
+    if runs == 0 {
+        return Err(Exit::Cli("--repeat-solve needs at least 1 run".to_string()));
+    }
+    let mut times = Vec::with_capacity(runs);
+    let mut first: Option<Solved> = None;
+    for run in 1..=runs {
+        let t0 = Instant::now();
+        let (cost, path) = solve_min(grid, cli, None)?;
+        times.push(t0.elapsed());
+        match &first {
+            None => first = Some((cost, path)),
+            Some((c0, _)) if *c0 != cost => {
+                return Err(Exit::Runtime(format!(
+                    "run {run} diverged: cost 0x{cost:X} ({cost}) but run 1 found 0x{c0:X} ({c0})"
+                )));
+            }
+            Some((_, p0)) if *p0 != path => {
+                let at = p0.iter().zip(&path).take_while(|(a, b)| a == b).count();
+                return Err(Exit::Runtime(format!(
+                    "run {run} diverged: same cost but a different path from step {at}"
+                )));
+            }
+            Some(_) => {}
+        }
     }
+    times.sort();
+    // Médiane basse pour un nombre pair de mesures: pas de moyenne de deux durées
+    let median = times[(runs - 1) / 2].as_secs_f64() * 1000.0;
+    let (cost, _) = first.unwrap_or_default();
+    println!("Min cost: 0x{cost:X} ({cost})");
+    println!("{runs} runs, all identical, median {median:.3} ms");
+    Ok(())
 }
 
-fn dijkstra_min_cost(grid: &Grid) -> Result<(u64, Vec<(usize, usize)>), String> {
-    let n = grid.w * grid.h;
-    let start = 0usize;
-    let goal = n - 1;
-
-    let mut dist = vec![u64::MAX; n];
-    let mut prev: Vec<Option<usize>> = vec![None; n];
-    let mut heap = BinaryHeap::new();
+// Aide au débogage: pourquoi le chemin passe (ou non) par ces cellules
+fn run_inspect(grid: &Grid, cells: &[(usize, usize)]) -> Result<(), Exit> {
+    for &(x, y) in cells {
+        if grid.idx(x, y).is_none() {
+            return Err(Exit::Cli(format!(
+                "--inspect {x},{y} is outside the {}x{} grid",
+                grid.w, grid.h
+            )));
+        }
+    }
 
-    dist[start] = 0;
-    heap.push(State {
-        cost: 0,
-        idx: start,
-    });
+    let from_start = distance_map(grid)?;
+    let (to_goal, next) = goal_distance_map(grid)?;
+    let cost = |d: Option<u64>| match d {
+        Some(d) => format!("0x{d:X} ({d})"),
+        None => "unreachable".to_string(),
+    };
 
-    while let Some(State { cost, idx }) = heap.pop() {
-        if cost != dist[idx] {
-            continue;
+    for (n, &(x, y)) in cells.iter().enumerate() {
+        let i = y * grid.w + x;
+        let v = grid.at(x, y).unwrap_or(0);
+        if n > 0 {
+            println!();
         }
-        if idx == goal {
-            break;
+        println!("CELL ({x},{y}): 0x{v:02X} ({v})");
+        if !grid.passable(x, y) {
+            println!("Passable: no (above --max-cell)");
         }
-
-        let x = idx % grid.w;
-        let y = idx / grid.w;
-
-        for (nx, ny) in neighbors4(x, y, grid.w, grid.h) {
-            let nidx = ny * grid.w + nx;
-            let w = grid.at(nx, ny).unwrap_or(0) as u64;
-            let next = cost.saturating_add(w);
-            if next < dist[nidx] {
-                dist[nidx] = next;
-                prev[nidx] = Some(idx);
-                heap.push(State {
-                    cost: next,
-                    idx: nidx,
-                });
+        println!("Min cost from start: {}", cost(from_start[i]));
+        println!("Min cost to goal: {}", cost(to_goal[i]));
+        match next[i] {
+            Some(j) => {
+                let (nx, ny) = (j % grid.w, j / grid.w);
+                let nv = grid.at(nx, ny).unwrap_or(0);
+                println!("Next toward goal: ({nx},{ny}) = 0x{nv:02X}");
             }
+            None if i == grid.w * grid.h - 1 => println!("Next toward goal: (this is the goal)"),
+            None => println!("Next toward goal: none"),
         }
+        let around: Vec<String> = [
+            ("up", y.checked_sub(1).map(|ny| (x, ny))),
+            ("down", Some((x, y + 1))),
+            ("left", x.checked_sub(1).map(|nx| (nx, y))),
+            ("right", Some((x + 1, y))),
+        ]
+        .into_iter()
+        .filter_map(|(dir, c)| {
+            let (cx, cy) = c?;
+            let cv = grid.at(cx, cy)?;
+            let blocked = if grid.passable(cx, cy) {
+                ""
+            } else {
+                " blocked"
+            };
+            Some(format!("{dir} ({cx},{cy})=0x{cv:02X}{blocked}"))
+        })
+        .collect();
+        println!("Neighbors: {}", around.join(", "));
     }
+    Ok(())
+}
+
+fn read_char_map(path: &Path) -> Result<Grid, Exit> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| Exit::Runtime(format!("failed to read '{}': {e}", path.display())))?;
+    parse_grid_chars(&content).map_err(|e| Exit::Cli(format!("'{}': {e}", path.display())))
+}
 
-    if dist[goal] == u64::MAX {
-        return Err("no path found".to_string());
+// Chemin "sur les crêtes": maximise la plus petite valeur rencontrée
+fn run_widest_path(grid: &Grid, cli: &Cli) -> Result<(), Exit> {
+    let (width, path) = widest_path(grid)?;
+    if cli.simple_check {
+        ensure_simple(&path, "widest")?;
     }
 
-    let path = reconstruct_path(prev, grid.w, goal);
-    Ok((dist[goal], path))
+    println!("WIDEST PATH (maximizes the lowest cell entered):");
+    println!("Clearance: 0x{width:02X} ({width})");
+    print_path_report(grid, path_cost(grid, &path), &path, cli.verbose_steps);
+    if cli.directions {
+        println!();
+        print_directions(&path);
+    }
+    if cli.visualize {
+        println!();
+        print_visualization(grid, &path, None, cli.palette);
+    }
+    Ok(())
 }
 
-/*MAX COST parmi les chemins à nombre de pas minimal*/
+fn run_check_path(grid: &Grid, path_file: &Path) -> Result<(), Exit> {
+    let content = fs::read_to_string(path_file)
+        .map_err(|e| Exit::Runtime(format!("failed to read '{}': {e}", path_file.display())))?;
+    let path = parse_path_text(&content).map_err(Exit::Cli)?;
 
-fn max_cost_among_shortest_paths(grid: &Grid) -> Option<(u64, Vec<(usize, usize)>)> {
-    let n = grid.w * grid.h;
-    let start = 0usize;
-    let goal = n - 1;
+    let cost = check_path(grid, &path).map_err(|e| Exit::Runtime(format!("invalid path: {e}")))?;
+    let (min_cost, _) = dijkstra_min_cost(grid)?;
 
-    // BFS pour distance en nombre de pas
-    let mut step = vec![i32::MAX; n];
-    let mut q = VecDeque::new();
-    step[start] = 0;
-    q.push_back(start);
+    println!("Path is valid ({} cells)", path.len());
+    println!("Total cost: 0x{:X} ({} decimal)", cost, cost);
+    if cost == min_cost {
+        println!("Optimal: yes");
+    } else {
+        println!(
+            "Optimal: no (minimum is 0x{:X} ({}), +{} over)",
+            min_cost,
+            min_cost,
+            cost - min_cost
+        );
+    }
+    Ok(())
+}
 
-    while let Some(idx) = q.pop_front() {
-        let x = idx % grid.w;
-        let y = idx / grid.w;
-        let d = step[idx];
+/*IO*/
 
-        for (nx, ny) in neighbors4(x, y, grid.w, grid.h) {
-            let nidx = ny * grid.w + nx;
-            if step[nidx] == i32::MAX {
-                step[nidx] = d + 1;
-                q.push_back(nidx);
+fn write_grid_file(path: &Path, grid: &Grid) -> Result<(), String> {
+    let mut out = String::new();
+    for y in 0..grid.h {
+        for x in 0..grid.w {
+            if x > 0 {
+                out.push(' ');
             }
+            let v = grid.at(x, y).unwrap_or(0);
+            out.push_str(&format!("{v:02X}"));
         }
+        out.push('\n');
     }
+    fs::write(path, out).map_err(|e| format!("failed to write '{}': {e}", path.display()))
+}
 
-    let goal_d = step[goal];
-    if goal_d == i32::MAX {
-        return None;
-    }
+fn write_grid_bin_file(path: &Path, grid: &Grid) -> Result<(), String> {
+    let file =
+        fs::File::create(path).map_err(|e| format!("failed to write '{}': {e}", path.display()))?;
+    write_grid_bin(io::BufWriter::new(file), grid)
+        .map_err(|e| format!("failed to write '{}': {e}", path.display()))
+}
 
-    // DP pour coût max sur le DAG des distances
-    let mut best = vec![i64::MIN; n];
-    let mut prev: Vec<Option<usize>> = vec![None; n];
-    best[start] = 0;
+/*Reporting / UI*/
 
-    let mut layers: Vec<Vec<usize>> = vec![Vec::new(); (goal_d as usize) + 1];
-    for (i, &d) in step.iter().enumerate() {
-        if d != i32::MAX {
-            layers[d as usize].push(i);
-        }
-    }
+fn format_distance_grid(grid: &Grid, dist: &[Option<u64>]) -> String {
+    let width = dist
+        .iter()
+        .flatten()
+        .map(|d| d.to_string().len())
+        .max()
+        .unwrap_or(2)
+        .max(2);
 
-    let limit = goal_d as usize;
-    for (d, layer) in layers.iter().enumerate().take(limit) {
-        for &idx in layer {
-            if best[idx] == i64::MIN {
-                continue;
+    let mut out = String::new();
+    for y in 0..grid.h {
+        for x in 0..grid.w {
+            if x > 0 {
+                out.push(' ');
             }
-            let x = idx % grid.w;
-            let y = idx / grid.w;
-            for (nx, ny) in neighbors4(x, y, grid.w, grid.h) {
-                let nidx = ny * grid.w + nx;
-                if step[nidx] == (d as i32) + 1 {
-                    let add = grid.at(nx, ny).unwrap_or(0) as i64;
-                    let cand = best[idx].saturating_add(add);
-                    if cand > best[nidx] {
-                        best[nidx] = cand;
-                        prev[nidx] = Some(idx);
-                    }
-                }
+            match dist[y * grid.w + x] {
+                Some(d) => out.push_str(&format!("{d:>width$}")),
+                None => out.push_str(&format!("{:>width$}", "--")),
             }
         }
+        if y + 1 < grid.h {
+            out.push('\n');
+        }
     }
-
-    if best[goal] == i64::MIN {
-        return None;
-    }
-
-    let path = reconstruct_path(prev, grid.w, goal);
-    Some((best[goal] as u64, path))
+    out
 }
 
-/*Reporting / UI*/
+fn format_distance_json(grid: &Grid, dist: &[Option<u64>]) -> String {
+    let rows: Vec<String> = (0..grid.h)
+        .map(|y| {
+            let cells: Vec<String> = dist[y * grid.w..(y + 1) * grid.w]
+                .iter()
+                .map(|d| d.map_or_else(|| "null".to_string(), |v| v.to_string()))
+                .collect();
+            format!("[{}]", cells.join(","))
+        })
+        .collect();
+    format!("[{}]", rows.join(","))
+}
 
-fn print_path_report(grid: &Grid, total: u64, path: &[(usize, usize)]) {
+fn print_path_report(grid: &Grid, total: u64, path: &[(usize, usize)], verbose_steps: bool) {
     println!("Total cost: 0x{:X} ({} decimal)", total, total);
     println!("Path length: {} steps", path.len());
     print!("Path: ");
@@ -497,20 +1068,129 @@ fn print_path_report(grid: &Grid, total: u64, path: &[(usize, usize)]) {
     println!();
     println!();
     println!("Step-by-step costs:");
-    println!("Start 0x00 (0,0)");
-    let mut acc = 0u64;
-    for &(x, y) in path.iter().skip(1) {
-        let v = grid.at(x, y).unwrap_or(0) as u64;
-        acc = acc.saturating_add(v);
-        println!("+ 0x{:02X} ({},{}) -> {}", v as u8, x, y, acc);
+    // 0x00 sauf avec --invert (0xFF)
+    println!("Start 0x{:02X} (0,0)", grid.at(0, 0).unwrap_or(0));
+    for (x, y, v, acc) in path_steps(grid, path) {
+        if verbose_steps {
+            println!(
+                "+ 0x{:02X} ({},{}) -> {} ({:.1}%)",
+                v,
+                x,
+                y,
+                acc,
+                percent_of(acc, total)
+            );
+        } else {
+            println!("+ 0x{:02X} ({},{}) -> {}", v, x, y, acc);
+        }
     }
     println!("Total: 0x{:X} ({})", total, total);
 }
 
+// Chaque case entrée après le départ: (x, y, valeur, coût cumulé)
+fn path_steps(grid: &Grid, path: &[(usize, usize)]) -> Vec<(usize, usize, u8, u64)> {
+    let mut acc = 0u64;
+    path.windows(2)
+        .map(|pair| {
+            let (x, y) = pair[1];
+            acc = acc.saturating_add(grid.step_weight(pair[0], pair[1]));
+            (x, y, grid.at(x, y).unwrap_or(0), acc)
+        })
+        .collect()
+}
+
+// [{"step":1,"cell":[x,y],"value":V,"cumulative":C}, ...], une étape par ligne
+fn format_trace_json(grid: &Grid, path: &[(usize, usize)]) -> String {
+    let steps: Vec<String> = path_steps(grid, path)
+        .into_iter()
+        .enumerate()
+        .map(|(i, (x, y, v, acc))| {
+            format!(
+                "  {{\"step\":{},\"cell\":[{x},{y}],\"value\":{v},\"cumulative\":{acc}}}",
+                i + 1
+            )
+        })
+        .collect();
+    if steps.is_empty() {
+        return "[]\n".to_string();
+    }
+    format!("[\n{}\n]\n", steps.join(",\n"))
+}
+
+// step,x,y,value_hex,value_dec,cumulative; le départ est l'étape 0 (cumul 0).
+// Avec `which`, une première colonne min/max distingue les deux chemins
+fn format_path_csv(grid: &Grid, paths: &[(&str, &[(usize, usize)])], which: bool) -> String {
+    let mut out = String::new();
+    if which {
+        out.push_str("which,");
+    }
+    out.push_str("step,x,y,value_hex,value_dec,cumulative\n");
+    for &(label, path) in paths {
+        let Some(&(sx, sy)) = path.first() else {
+            continue;
+        };
+        let start = (sx, sy, grid.at(sx, sy).unwrap_or(0), 0);
+        let rows = std::iter::once(start).chain(path_steps(grid, path));
+        for (i, (x, y, v, acc)) in rows.enumerate() {
+            if which {
+                out.push_str(label);
+                out.push(',');
+            }
+            out.push_str(&format!("{i},{x},{y},{v:02X},{v},{acc}\n"));
+        }
+    }
+    out
+}
+
+// Écrit dans PATH.tmp puis renomme: jamais de fichier à moitié écrit à PATH
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, bytes)
+        .and_then(|()| fs::rename(&tmp, path))
+        .map_err(|e| {
+            let _ = fs::remove_file(&tmp);
+            format!("failed to write '{}': {e}", path.display())
+        })
+}
+
+fn print_directions(path: &[(usize, usize)]) {
+    println!("Directions:");
+    let moves = path_directions(path);
+    if moves.is_empty() {
+        println!("No moves (start is the goal)");
+        return;
+    }
+    for (i, (dir, n)) in moves.iter().enumerate() {
+        println!("{}. {dir} {n}", i + 1);
+    }
+}
+
+fn print_baseline(grid: &Grid, optimal: u64) -> Result<(), Exit> {
+    let (greedy, _) = greedy_walk(grid).map_err(Exit::Runtime)?;
+    println!(
+        "Greedy baseline: 0x{:X}, optimal: 0x{:X} ({:.1}% better)",
+        greedy,
+        optimal,
+        percent_of(greedy.saturating_sub(optimal), greedy)
+    );
+    Ok(())
+}
+
+fn percent_of(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
 fn print_visualization(
     grid: &Grid,
     min_path: &[(usize, usize)],
     max_path: Option<&[(usize, usize)]>,
+    palette: Palette,
 ) {
     let use_color = io::stdout().is_terminal();
 
@@ -530,7 +1210,11 @@ fn print_visualization(
         }
     }
 
-    println!("HEX GRID:");
+    if use_color {
+        println!("HEX GRID ({palette} palette):");
+    } else {
+        println!("HEX GRID:");
+    }
     for y in 0..grid.h {
         for x in 0..grid.w {
             if x > 0 {
@@ -547,7 +1231,7 @@ fn print_visualization(
                     // chemin min en blanc
                     print!("\x1b[97m{:02X}\x1b[0m", v);
                 } else {
-                    let c = rainbow_ansi256(v);
+                    let c = value_to_ansi(v, palette);
                     print!("\x1b[38;5;{}m{:02X}\x1b[0m", c, v);
                 }
             } else {
@@ -556,6 +1240,96 @@ fn print_visualization(
         }
         println!();
     }
+
+    if use_color {
+        println!();
+        print_legend(max_path.is_some(), palette);
+    }
+}
+
+// Largeur maximale de l'aperçu, et seuil de moyenne au-delà duquel un point est allumé
+const PREVIEW_COLS: usize = 64;
+const PREVIEW_THRESHOLD: u8 = 0x7F;
+
+fn print_preview(grid: &Grid, path: Option<&[(usize, usize)]>) {
+    let scale = preview_scale(grid, PREVIEW_COLS);
+    println!(
+        "PREVIEW (1 dot = {scale}x{scale} cells, set when the average exceeds 0x{PREVIEW_THRESHOLD:02X}{}):",
+        if path.is_some() {
+            " or the min path crosses it"
+        } else {
+            ""
+        }
+    );
+    for line in braille_preview(grid, scale, PREVIEW_THRESHOLD, path) {
+        println!("{line}");
+    }
+}
+
+// Légende générée depuis value_to_ansi pour rester cohérente avec la grille
+fn print_legend(with_max: bool, palette: Palette) {
+    print!("Legend: \x1b[97mXX\x1b[0m min path");
+    if with_max {
+        print!(", \x1b[31mXX\x1b[0m max path");
+    }
+    println!(", other cells colored by value");
+    print!("Scale (low -> high):");
+    // Six échantillons régulièrement espacés (un par niveau de l'arc-en-ciel)
+    for v in [0x00u8, 0x33, 0x66, 0x99, 0xCC, 0xFF] {
+        print!(" \x1b[38;5;{}m{:02X}\x1b[0m", value_to_ansi(v, palette), v);
+    }
+    println!();
+}
+
+// Palettes de --visualize, toutes exprimées en couleurs ANSI 256
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Palette {
+    Rainbow,
+    Grayscale,
+    Heat,
+}
+
+impl std::str::FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "rainbow" => Ok(Palette::Rainbow),
+            "grayscale" | "greyscale" => Ok(Palette::Grayscale),
+            "heat" => Ok(Palette::Heat),
+            _ => Err(format!(
+                "invalid palette '{s}' (expected rainbow, grayscale or heat)"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Palette {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Palette::Rainbow => "rainbow",
+            Palette::Grayscale => "grayscale",
+            Palette::Heat => "heat",
+        })
+    }
+}
+
+fn value_to_ansi(v: u8, palette: Palette) -> u8 {
+    match palette {
+        Palette::Rainbow => rainbow_ansi256(v),
+        // Rampe de gris 232..=255 (24 niveaux)
+        Palette::Grayscale => 232 + ((v as u16 * 23) / 255) as u8,
+        Palette::Heat => heat_ansi256(v),
+    }
+}
+
+// Noir -> rouge -> jaune -> blanc: on monte r, puis g, puis b dans le cube 6x6x6
+fn heat_ansi256(v: u8) -> u8 {
+    let level = (v as u16 * 15 / 255) as u8;
+    let r = level.min(5);
+    let g = level.saturating_sub(5).min(5);
+    let b = level.saturating_sub(10).min(5);
+    16 + 36 * r + 6 * g + b
 }
 
 fn rainbow_ansi256(v: u8) -> u8 {
@@ -566,6 +1340,76 @@ fn rainbow_ansi256(v: u8) -> u8 {
     16 + 36 * r + 6 * g + b
 }
 
+// Nombre de trames visé pour une recherche complète, et pause entre deux trames
+const ANIM_FRAMES: usize = 40;
+const ANIM_FRAME_DELAY: Duration = Duration::from_millis(25);
+// Trames récentes prises en compte pour le débit (et donc l'ETA)
+const ANIM_RATE_WINDOW: usize = 5;
+
+// Balayage Dijkstra sur un terminal: une trame réécrite (\r) toutes les quelques cases
+// fixées, avec la fraction fixée et l'ETA. La recherche est enregistrée puis rejouée:
+// les pauses restent hors du budget de --time-limit
+fn run_frontier_animation(grid: &Grid) {
+    println!("Searching for minimum cost path...");
+    let total = grid.w * grid.h;
+    let every = total.div_ceil(ANIM_FRAMES).max(1);
+    // (instant de la résolution, cellules fixées, cellule) de chaque trame
+    let mut frames: Vec<(Instant, usize, (usize, usize))> = Vec::new();
+    let mut last = (0, (0, 0));
+    let started = Instant::now();
+    let res = dijkstra_min_cost_observed(grid, |settled, cell| {
+        last = (settled, cell);
+        if settled.is_multiple_of(every) {
+            frames.push((Instant::now(), settled, cell));
+        }
+    });
+    let (last, last_cell) = last;
+    if frames.last().is_none_or(|&(_, settled, _)| settled != last) {
+        frames.push((Instant::now(), last, last_cell));
+    }
+    let path = match res {
+        Ok((_, path)) => path,
+        Err(e) => {
+            println!("Animation stopped: {}", e);
+            return;
+        }
+    };
+
+    // Rejouée après coup: débit et ETA viennent des instants de la résolution,
+    // rapportés au nombre final de cellules fixées
+    let mut recent: VecDeque<(Instant, usize)> = VecDeque::from([(started, 0)]);
+    let mut out = io::stdout();
+    for (now, settled, (x, y)) in frames {
+        let (t0, s0) = recent[0];
+        let rate = (settled - s0) as f64 / now.duration_since(t0).as_secs_f64().max(1e-9);
+        let eta = (last - settled) as f64 / rate;
+        let _ = write!(
+            out,
+            "\r\x1b[KFrontier: {}/{} cells ({}%), ETA {:.1}s  exploring ({},{})",
+            settled,
+            last,
+            settled * 100 / last.max(1),
+            eta,
+            x,
+            y
+        );
+        let _ = out.flush();
+        recent.push_back((now, settled));
+        if recent.len() > ANIM_RATE_WINDOW {
+            recent.pop_front();
+        }
+        std::thread::sleep(ANIM_FRAME_DELAY);
+    }
+
+    let _ = write!(out, "\r\x1b[K");
+    println!(
+        "Goal reached after {}/{} settled cells ({} steps on the path)",
+        last,
+        total,
+        path.len() - 1
+    );
+}
+
 fn run_light_animation(grid: &Grid) {
     println!("Searching for minimum cost path...");
     let n = grid.w * grid.h;
@@ -584,7 +1428,7 @@ fn run_light_animation(grid: &Grid) {
             println!("[Animation continues...]");
             break;
         }
-        for (nx, ny) in neighbors4(x, y, grid.w, grid.h) {
+        for (nx, ny) in grid.open_neighbors(x, y) {
             let nidx = ny * grid.w + nx;
             if !seen[nidx] {
                 seen[nidx] = true;
@@ -593,33 +1437,3 @@ fn run_light_animation(grid: &Grid) {
         }
     }
 }
-
-/*util*/
-
-fn neighbors4(x: usize, y: usize, w: usize, h: usize) -> Vec<(usize, usize)> {
-    let mut out = Vec::with_capacity(4);
-    if y > 0 {
-        out.push((x, y - 1));
-    }
-    if y + 1 < h {
-        out.push((x, y + 1));
-    }
-    if x > 0 {
-        out.push((x - 1, y));
-    }
-    if x + 1 < w {
-        out.push((x + 1, y));
-    }
-    out
-}
-
-fn reconstruct_path(prev: Vec<Option<usize>>, w: usize, goal: usize) -> Vec<(usize, usize)> {
-    let mut out = Vec::new();
-    let mut cur = Some(goal);
-    while let Some(i) = cur {
-        out.push((i % w, i / w));
-        cur = prev[i];
-    }
-    out.reverse();
-    out
-}